@@ -0,0 +1,196 @@
+// command line argument parsing
+use std::ffi::OsString;
+use std::path::PathBuf;
+use crate::shell_init;
+
+pub const USAGE: &str = "\
+Usage: dirman [OPTIONS] [DIRECTORY]
+
+Arguments:
+  [DIRECTORY]          directory to open (defaults to the current directory)
+
+Options:
+  --max-depth <N>      only read the directory tree in the background up to N levels deep
+  --show-hidden        show hidden files and directories
+  --config <FILE>      read configuration from FILE
+  --no-color           disable colored output
+  --cwd-file <FILE>    write the current directory to FILE when quitting
+  --shell-init <SHELL> print a function for bash, zsh or fish which starts dirman and then
+                       changes to the directory it quit in
+  -h, --help           print this help message and exit
+  -V, --version        print version information and exit";
+
+// options that the program was started with
+pub struct Args {
+    pub path: Option<PathBuf>,
+    pub max_depth: Option<usize>,
+    pub show_hidden: bool,
+    pub config: Option<PathBuf>,
+    pub no_color: bool,
+    pub cwd_file: Option<PathBuf>,
+}
+
+// what the program should do as requested by the command line
+pub enum Invocation {
+    Run(Args),
+    Help,
+    Version,
+    // print the shell function for the named shell
+    ShellInit(String),
+}
+
+// parses the program arguments (not including the program name); returns a message
+// describing the problem if they are invalid
+pub fn parse<I: IntoIterator<Item = OsString>>(args: I) -> Result<Invocation, String> {
+    let mut parsed = Args {
+        path: None,
+        max_depth: None,
+        show_hidden: false,
+        config: None,
+        no_color: false,
+        cwd_file: None,
+    };
+
+    let mut args = args.into_iter();
+    let mut only_positional = false;
+    while let Some(arg) = args.next() {
+        // options are always valid unicode; anything else is a path
+        let arg_str = match arg.to_str() {
+            Some(s) if !only_positional && s.starts_with('-') && s != "-" => s.to_string(),
+            _ => {
+                if parsed.path.is_some() {
+                    return Err(format!("unexpected argument '{}'", arg.to_string_lossy()));
+                }
+                parsed.path = Some(PathBuf::from(arg));
+                continue;
+            },
+        };
+
+        // options may be given as '--option value' or '--option=value'
+        let (name, inline_value) = match arg_str.find('=') {
+            Some(i) if arg_str.starts_with("--") => (&arg_str[..i], Some(OsString::from(&arg_str[i + 1..]))),
+            _ => (arg_str.as_str(), None),
+        };
+
+        let takes_value = matches!(name, "--max-depth" | "--config" | "--cwd-file" | "--shell-init");
+        if !takes_value && inline_value.is_some() {
+            return Err(format!("option '{}' does not take a value", name));
+        }
+
+        let mut value = |name: &str| -> Result<OsString, String> {
+            inline_value.clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("option '{}' requires a value", name))
+        };
+
+        match name {
+            "--" => only_positional = true,
+            "-h" | "--help" => return Ok(Invocation::Help),
+            "-V" | "--version" => return Ok(Invocation::Version),
+            "--show-hidden" => parsed.show_hidden = true,
+            "--no-color" => parsed.no_color = true,
+            "--max-depth" => {
+                let depth = value(name)?;
+                let depth = depth.to_str()
+                    .and_then(|d| d.parse::<usize>().ok())
+                    .ok_or_else(|| format!("invalid value '{}' for '--max-depth'; expected a number",
+                        depth.to_string_lossy()))?;
+                parsed.max_depth = Some(depth);
+            },
+            "--config" => parsed.config = Some(PathBuf::from(value(name)?)),
+            "--cwd-file" => parsed.cwd_file = Some(PathBuf::from(value(name)?)),
+            "--shell-init" => {
+                let shell = value(name)?.to_string_lossy().into_owned();
+                if shell_init::function(&shell).is_none() {
+                    return Err(format!("unknown shell '{}' for '--shell-init'; expected one of {}",
+                        shell, shell_init::SHELLS.join(", ")));
+                }
+                return Ok(Invocation::ShellInit(shell));
+            },
+            _ => return Err(format!("unknown option '{}'", name)),
+        }
+    }
+
+    Ok(Invocation::Run(parsed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invocation(args: &[&str]) -> Result<Invocation, String> {
+        parse(args.iter().map(OsString::from))
+    }
+
+    fn run(args: &[&str]) -> Args {
+        match invocation(args) {
+            Ok(Invocation::Run(parsed)) => parsed,
+            Ok(_) => panic!("{:?} did not run", args),
+            Err(message) => panic!("{:?}: {}", args, message),
+        }
+    }
+
+    fn error(args: &[&str]) -> String {
+        match invocation(args) {
+            Ok(_) => panic!("{:?} was accepted", args),
+            Err(message) => message,
+        }
+    }
+
+    #[test]
+    fn defaults() {
+        let parsed = run(&[]);
+        assert_eq!(parsed.path, None);
+        assert_eq!(parsed.max_depth, None);
+        assert!(!parsed.show_hidden && !parsed.no_color);
+        assert_eq!(parsed.config, None);
+    }
+
+    #[test]
+    fn options_and_path() {
+        let parsed = run(&["--show-hidden", "projects", "--max-depth", "3", "--no-color", "--config=dirman.toml"]);
+        assert_eq!(parsed.path, Some(PathBuf::from("projects")));
+        assert_eq!(parsed.max_depth, Some(3));
+        assert!(parsed.show_hidden && parsed.no_color);
+        assert_eq!(parsed.config, Some(PathBuf::from("dirman.toml")));
+        assert_eq!(run(&["--max-depth=0"]).max_depth, Some(0));
+    }
+
+    #[test]
+    fn paths_looking_like_options() {
+        assert_eq!(run(&["-"]).path, Some(PathBuf::from("-")));
+        assert_eq!(run(&["--", "--show-hidden"]).path, Some(PathBuf::from("--show-hidden")));
+        assert!(!run(&["--", "--show-hidden"]).show_hidden);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_unicode_path() {
+        use std::os::unix::ffi::OsStringExt;
+        let path = OsString::from_vec(vec![b'-', 0xFF]);
+        match parse(vec![path.clone()]) {
+            Ok(Invocation::Run(parsed)) => assert_eq!(parsed.path, Some(PathBuf::from(path))),
+            _ => panic!("the path was not accepted"),
+        }
+    }
+
+    #[test]
+    fn help_and_version() {
+        assert!(matches!(invocation(&["--help"]), Ok(Invocation::Help)));
+        assert!(matches!(invocation(&["dir", "-h", "--bogus"]), Ok(Invocation::Help)));
+        assert!(matches!(invocation(&["-V"]), Ok(Invocation::Version)));
+        assert!(matches!(invocation(&["--version"]), Ok(Invocation::Version)));
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(error(&["--max-depth", "deep"]), "invalid value 'deep' for '--max-depth'; expected a number");
+        assert_eq!(error(&["--max-depth=-1"]), "invalid value '-1' for '--max-depth'; expected a number");
+        assert_eq!(error(&["--max-depth"]), "option '--max-depth' requires a value");
+        assert_eq!(error(&["--config"]), "option '--config' requires a value");
+        assert_eq!(error(&["--show-hidden=yes"]), "option '--show-hidden' does not take a value");
+        assert_eq!(error(&["--colour"]), "unknown option '--colour'");
+        assert_eq!(error(&["-x"]), "unknown option '-x'");
+        assert_eq!(error(&["one", "two"]), "unexpected argument 'two'");
+    }
+}
@@ -0,0 +1,165 @@
+// the columns shown before the names in the contents panel, chosen with 'columns' in the
+// [listing] section of the configuration; each column is as wide as its widest entry, and the
+// widest columns are cut short (with '…') when they would leave too little room for the names
+use std::ffi::OsStr;
+use std::fs::Metadata;
+use std::path::Path;
+use crate::config::Config;
+use crate::platform::{FileKind, PlatformMetadata};
+use crate::StateManager;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Column {
+    Modified,
+    Created,
+    Accessed,
+    Size,
+    Permissions,
+    Owner,
+    Group,
+    Links,
+    Inode,
+    Type,
+    Extension,
+}
+
+// columns are kept at least this wide when they are cut short
+const MIN_WIDTH: usize = 4;
+// room kept for the names when columns are cut short
+const NAME_ROOM: usize = 16;
+
+impl Column {
+    pub const NAMES: &'static [&'static str] = &[
+        "modified", "created", "accessed", "size", "permissions", "owner", "group", "links", "inode", "type", "ext",
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        const COLUMNS: &[Column] = &[
+            Column::Modified, Column::Created, Column::Accessed, Column::Size, Column::Permissions, Column::Owner,
+            Column::Group, Column::Links, Column::Inode, Column::Type, Column::Extension,
+        ];
+        Self::NAMES.iter().position(|column| *column == name).map(|i| COLUMNS[i])
+    }
+
+    fn heading(self) -> &'static str {
+        match self {
+            Column::Modified => "Last Modified",
+            Column::Created => "Created",
+            Column::Accessed => "Last Accessed",
+            Column::Size => "Size",
+            Column::Permissions => "Permissions",
+            Column::Owner => "Owner",
+            Column::Group => "Group",
+            Column::Links => "Links",
+            Column::Inode => "Inode",
+            Column::Type => "Type",
+            Column::Extension => "Ext",
+        }
+    }
+
+    // numbers are aligned to the right
+    fn right_aligned(self) -> bool {
+        matches!(self, Column::Size | Column::Links | Column::Inode)
+    }
+
+    // the text of this column for an entry; blank if the platform does not provide it
+    fn cell(self, config: &Config, entry: &Entry) -> String {
+        let meta = match entry.meta {
+            Some(meta) => meta,
+            // the size is the only column which says it is unknown, as it always has
+            None => return String::from(if self == Column::Size && !entry.is_dir { "?" } else { "" }),
+        };
+        match self {
+            Column::Modified => config.format_date(meta.timestamps().modified),
+            Column::Created => config.format_date(meta.timestamps().created),
+            Column::Accessed => config.format_date(meta.timestamps().accessed),
+            Column::Size if entry.is_dir => String::new(),
+            Column::Size => config.format_size(meta.file_size()),
+            Column::Permissions => meta.permissions_string(),
+            Column::Owner => meta.owner_name(),
+            Column::Group => meta.group_name(),
+            Column::Links => meta.link_count().map_or(String::new(), |count| count.to_string()),
+            Column::Inode => meta.inode().map_or(String::new(), |inode| inode.to_string()),
+            Column::Type if entry.is_link && meta.file_kind() != FileKind::Link => String::from("link"),
+            Column::Type => String::from(match meta.file_kind() {
+                FileKind::Directory => "dir",
+                FileKind::Regular => "file",
+                FileKind::Executable => "exec",
+                FileKind::Setuid => "setuid",
+                FileKind::Setgid => "setgid",
+                FileKind::Link => "broken link",
+                FileKind::Fifo => "fifo",
+                FileKind::Socket => "socket",
+                FileKind::BlockDevice => "block",
+                FileKind::CharDevice => "char",
+            }),
+            Column::Extension if entry.is_dir => String::new(),
+            Column::Extension => Path::new(entry.name).extension().map_or(String::new(), |ext| ext.to_string_lossy().into_owned()),
+        }
+    }
+}
+
+// what the columns of an entry are made from; `meta` is that of the entry, or of its target if
+// it is a symbolic link
+pub struct Entry<'e> {
+    pub name: &'e OsStr,
+    pub meta: Option<&'e Metadata>,
+    pub is_dir: bool,
+    pub is_link: bool,
+}
+
+// the columns of the listed entries, laid out
+pub struct Columns {
+    pub heading: String,
+    pub underline: String,
+    // for each entry, in the order given; each ends with the space before the name
+    pub rows: Vec<String>,
+}
+
+impl<'a> StateManager<'a> {
+    pub fn lay_out_columns(&self, entries: &[Entry]) -> Columns {
+        let columns = &self.config.columns;
+        let cells: Vec<Vec<String>> = entries.iter()
+            .map(|entry| columns.iter().map(|column| column.cell(&self.config, entry)).collect())
+            .collect();
+        let mut widths: Vec<usize> = columns.iter().enumerate()
+            .map(|(i, column)| cells.iter().map(|row| row[i].chars().count()).fold(column.heading().chars().count(), usize::max))
+            .collect();
+
+        // the gutter before the columns takes 3 characters, and each column is followed by 2 spaces
+        let available = self.dir_contents.contents_size().x.saturating_sub(3 + NAME_ROOM);
+        while widths.iter().map(|width| width + 2).sum::<usize>() > available {
+            match widths.iter_mut().filter(|width| **width > MIN_WIDTH).max() {
+                Some(widest) => *widest -= 1,
+                // what does not fit can still be scrolled to
+                None => break,
+            }
+        }
+
+        let line = |texts: &mut dyn Iterator<Item = String>| -> String {
+            columns.iter().zip(&widths).zip(texts)
+                .map(|((column, width), text)| {
+                    let text = truncate(&text, *width);
+                    if column.right_aligned() {
+                        format!("{:>width$}  ", text, width = width)
+                    } else {
+                        format!("{:width$}  ", text, width = width)
+                    }
+                })
+                .collect()
+        };
+        Columns {
+            heading: line(&mut columns.iter().map(|column| column.heading().to_string())),
+            underline: line(&mut columns.iter().zip(&widths).map(|(column, width)| "‾".repeat(column.heading().chars().count().min(*width)))),
+            rows: cells.into_iter().map(|row| line(&mut row.into_iter())).collect(),
+        }
+    }
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_string()
+    } else {
+        text.chars().take(width - 1).chain(std::iter::once('…')).collect()
+    }
+}
@@ -0,0 +1,184 @@
+// splitting of a command line into arguments
+//
+// arguments are separated by whitespace; whitespace and quotes can be included in an argument by
+// quoting it or by escaping them with a backslash:
+//   'single quotes'   everything up to the next single quote is taken literally
+//   "double quotes"   backslash escapes are processed, everything else is taken literally
+//   \<c>              a literal space, quote or backslash (outside of single quotes)
+// the name escapes from the names module (e.g. '\xFF') are also understood outside of single
+// quotes; a backslash followed by anything else is kept as it is
+use std::ffi::{OsStr, OsString};
+use crate::names::{self, NameBuilder};
+
+enum Quote {
+    None,
+    Single,
+    Double,
+}
+
+// splits a command line into arguments; returns a message describing the problem if the
+// command line is malformed
+pub fn tokenize(line: &str) -> Result<Vec<OsString>, String> {
+    let mut tokens = vec![];
+    // the argument currently being read, if any (an empty quoted argument is still an argument)
+    let mut token: Option<NameBuilder> = None;
+    let mut quote = Quote::None;
+
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        let mut len = c.len_utf8();
+        match quote {
+            Quote::Single => match c {
+                '\'' => quote = Quote::None,
+                c => token.get_or_insert_with(NameBuilder::default).push_char(c),
+            },
+            Quote::None | Quote::Double => match c {
+                '\\' => {
+                    let builder = token.get_or_insert_with(NameBuilder::default);
+                    len = escape(rest, &quote, builder);
+                },
+                '"' => {
+                    token.get_or_insert_with(NameBuilder::default);
+                    quote = match quote {
+                        Quote::Double => Quote::None,
+                        _ => Quote::Double,
+                    };
+                },
+                '\'' if matches!(quote, Quote::None) => {
+                    token.get_or_insert_with(NameBuilder::default);
+                    quote = Quote::Single;
+                },
+                c if c.is_whitespace() && matches!(quote, Quote::None) => {
+                    if let Some(builder) = token.take() {
+                        tokens.push(builder.finish());
+                    }
+                },
+                c => token.get_or_insert_with(NameBuilder::default).push_char(c),
+            },
+        }
+        rest = &rest[len..];
+    }
+
+    match quote {
+        Quote::Single => return Err(String::from("Unterminated single quote")),
+        Quote::Double => return Err(String::from("Unterminated double quote")),
+        Quote::None => {},
+    }
+
+    if let Some(builder) = token {
+        tokens.push(builder.finish());
+    }
+    Ok(tokens)
+}
+
+// writes a name as an argument which tokenize reads back as the same name, e.g. 'My\ Notes.txt'
+pub fn quote(name: &OsStr) -> String {
+    let shown = names::display_name(name);
+    // names shown with escape sequences already have their backslashes escaped
+    let escaped = Some(shown.as_str()) != name.to_str();
+    let mut quoted = String::new();
+    for c in shown.chars() {
+        if (c == '\\' && !escaped) || c == '"' || c == '\'' || c.is_whitespace() {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted
+}
+
+// handles a backslash at the start of the text; returns how much of the text was consumed
+fn escape(text: &str, quote: &Quote, builder: &mut NameBuilder) -> usize {
+    if let Some((unit, len)) = names::parse_escape(text) {
+        builder.push_raw(unit);
+        return len;
+    }
+
+    match text[1..].chars().next() {
+        // inside double quotes only the characters which would otherwise be special are escaped
+        Some(c @ ('\\' | '"')) => {
+            builder.push_char(c);
+            1 + c.len_utf8()
+        },
+        Some(c) if matches!(quote, Quote::None) && (c == '\'' || c.is_whitespace()) => {
+            builder.push_char(c);
+            1 + c.len_utf8()
+        },
+        _ => {
+            builder.push_char('\\');
+            1
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(line: &str) -> Vec<OsString> {
+        tokenize(line).unwrap()
+    }
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn splits_on_whitespace() {
+        assert_eq!(tokens("mv  a\tb "), args(&["mv", "a", "b"]));
+        assert_eq!(tokens("   "), args(&[]));
+    }
+
+    #[test]
+    fn quotes() {
+        assert_eq!(tokens("cd 'My Documents'"), args(&["cd", "My Documents"]));
+        assert_eq!(tokens("cd \"My Documents\""), args(&["cd", "My Documents"]));
+        assert_eq!(tokens("a'b c'\"d e\""), args(&["ab cd e"]));
+        // an empty quoted argument is still an argument
+        assert_eq!(tokens("touch '' \"\""), args(&["touch", "", ""]));
+        assert_eq!(tokens("echo \"it's\" 'say \"hi\"'"), args(&["echo", "it's", "say \"hi\""]));
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(tokens(r"cd My\ Documents"), args(&["cd", "My Documents"]));
+        assert_eq!(tokens(r#"a\'b a\"b a\\b"#), args(&["a'b", "a\"b", r"a\b"]));
+        // inside double quotes only backslashes and double quotes are escaped
+        assert_eq!(tokens(r#""a\"b\\c\'d""#), args(&[r#"a"b\c\'d"#]));
+        // nothing is escaped inside single quotes
+        assert_eq!(tokens(r"'a\ b'"), args(&[r"a\ b"]));
+        // a backslash before anything else is kept
+        assert_eq!(tokens(r"a\qb c\"), args(&[r"a\qb", r"c\"]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn name_escapes() {
+        use std::os::unix::ffi::OsStringExt;
+        assert_eq!(tokens(r"rm a\xFFb"), vec![OsString::from("rm"), OsString::from_vec(vec![b'a', 0xFF, b'b'])]);
+        assert_eq!(tokens(r"'a\xFF'"), args(&[r"a\xFF"]));
+    }
+
+    #[test]
+    fn unterminated_quotes() {
+        assert_eq!(tokenize("cd 'My Documents"), Err(String::from("Unterminated single quote")));
+        assert_eq!(tokenize("cd \"My Documents"), Err(String::from("Unterminated double quote")));
+        assert_eq!(tokenize("cd \"it's"), Err(String::from("Unterminated double quote")));
+    }
+
+    #[test]
+    fn quote_round_trips() {
+        let names = ["plain.txt", "My Notes.txt", "it's", "say \"hi\"", r"back\slash", r"\xFF", "tab\there", "ünïcødé", " "];
+        for name in names {
+            let line = format!("rename {} x", quote(OsStr::new(name)));
+            assert_eq!(tokens(&line), args(&["rename", name, "x"]), "quoted as {}", line);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn quote_round_trips_invalid_unicode() {
+        use std::os::unix::ffi::OsStringExt;
+        let name = OsString::from_vec(vec![b'a', 0xFF, b' ', b'\\', 0x01]);
+        assert_eq!(tokens(&quote(&name)), vec![name]);
+    }
+}
@@ -0,0 +1,465 @@
+// the configuration file: a TOML file given with --config, or config.toml in the program's
+// configuration directory (e.g. ~/.config/dirman/config.toml on unix) if it exists
+//
+//   [layout]
+//   tree-width = 50          # percent of the width taken by the tree
+//   preview-width = 33       # percent of the width taken by the preview pane, when it is open
+//   scroll-step = 5          # rows or columns scrolled at a time
+//
+//   [theme]
+//   name = "default"         # default, high-contrast, monochrome or solarized
+//   colors = "auto"          # auto, none, 16, 256 or truecolor
+//   file-colors = true       # color names by kind and extension (see file_colors.rs)
+//
+//   [colors]                 # changes to the theme; see theme.rs for roles and styles
+//   current-dir = "bold blue"
+//
+//   [format]
+//   date = "%m/%d/%Y %I:%M %p"   # strftime format of dates in the contents panel
+//   size = "binary"              # binary (KB = 1024 bytes), decimal (kB = 1000 bytes) or bytes
+//
+//   [listing]
+//   sort = "name"            # name, size, mtime or ext
+//   descending = false
+//   natural = false          # order numbers in names by value ('file2' before 'file10')
+//   ignore-case = false
+//   columns = ["modified", "size"]   # see columns.rs
+//   show-hidden = false
+//
+//   [confirm]                # which actions ask for confirmation first
+//   permanent-remove = true
+//   pattern = true           # moving or removing the entries matched by a pattern
+//   rename-batch = true
+//   empty-trash = true
+//
+//   [keys]                   # see keymap.rs
+use std::ffi::OsStr;
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use chrono::{DateTime, TimeZone, Utc};
+use chrono::format::{Item, StrftimeItems};
+use crate::columns::Column;
+use crate::file_colors::FileColors;
+use crate::keymap::Keymap;
+use crate::listing::{Sort, SortKey};
+use crate::platform;
+use crate::scan::Scanner;
+use crate::theme::{self, Color, Depth, Theme};
+use crate::{CurrentArea, StateManager};
+
+pub struct Config {
+    // the file given with --config, which 'reload-config' reads again
+    pub file: Option<PathBuf>,
+    pub layout: Layout,
+    pub theme: Theme,
+    // how many colors to use; None to work it out from the terminal
+    pub color_depth: Option<Depth>,
+    // None if names are not colored by their kind
+    pub file_colors: Option<FileColors>,
+    pub date_format: String,
+    // width of the dates in the contents panel, at least that of the 'Last Modified' heading
+    pub date_width: usize,
+    pub size_format: SizeFormat,
+    pub sort: Sort,
+    pub show_hidden: bool,
+    // columns shown before the names in the contents panel
+    pub columns: Vec<Column>,
+    pub confirm: Confirm,
+    pub keymap: Keymap,
+}
+
+pub struct Layout {
+    // percentages of the terminal's width
+    pub tree_width: usize,
+    pub preview_width: usize,
+    pub scroll_step: usize,
+}
+
+#[derive(Clone, Copy)]
+pub enum SizeFormat {
+    Binary,
+    Decimal,
+    Bytes,
+}
+
+pub struct Confirm {
+    pub permanent_remove: bool,
+    pub pattern: bool,
+    pub rename_batch: bool,
+    pub empty_trash: bool,
+}
+
+// the narrowest a panel can be, including its margins
+pub const MIN_PANEL_WIDTH: usize = 4;
+pub const MIN_WINDOW_WIDTH: usize = 75;
+pub const MIN_WINDOW_HEIGHT: usize = 10;
+
+const SECTIONS: &[&str] = &["layout", "theme", "colors", "format", "listing", "confirm", "keys"];
+
+impl Default for Config {
+    // the configuration used when there is no configuration file
+    fn default() -> Self {
+        Self {
+            file: None,
+            layout: Layout { tree_width: 50, preview_width: 33, scroll_step: 5 },
+            theme: Theme::builtin("default").unwrap(),
+            color_depth: None,
+            file_colors: Some(FileColors::from_env()),
+            date_format: String::from("%m/%d/%Y %I:%M %p"),
+            date_width: 19,
+            size_format: SizeFormat::Binary,
+            sort: Sort { key: SortKey::Name, descending: false, natural: false, ignore_case: false },
+            show_hidden: false,
+            columns: vec![Column::Modified, Column::Size],
+            confirm: Confirm { permanent_remove: true, pattern: true, rename_batch: true, empty_trash: true },
+            keymap: Keymap::preset("default").unwrap(),
+        }
+    }
+}
+
+impl Config {
+    // reads the configuration file; returns a message describing the problem if it cannot be
+    // read or is invalid
+    pub fn load(file: Option<&Path>) -> Result<Self, String> {
+        let mut config = match file.map(Path::to_path_buf).or_else(default_path) {
+            Some(path) => {
+                let text = fs::read_to_string(&path)
+                    .map_err(|error| format!("cannot read config file '{}': {}", path.display(), error))?;
+                Self::parse(&text).map_err(|message| format!("error in config file '{}': {}", path.display(), message))?
+            },
+            None => Self::default(),
+        };
+        config.file = file.map(Path::to_path_buf);
+        Ok(config)
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        // errors are shown on a single line
+        let table = text.parse::<toml::Table>().map_err(|error| {
+            let message = error.message().trim().replace('\n', "; ");
+            match error.span() {
+                Some(span) => format!("line {}: {}", text[..span.start].matches('\n').count() + 1, message),
+                None => message,
+            }
+        })?;
+        if let Some(name) = table.keys().find(|name| !SECTIONS.contains(&name.as_str())) {
+            return Err(format!("unknown section [{}]; expected one of {}", name, SECTIONS.join(", ")));
+        }
+        let mut config = Self::default();
+
+        let layout = Section::of(&table, "layout", &["tree-width", "preview-width", "scroll-step"])?;
+        config.layout.tree_width = layout.integer("tree-width", config.layout.tree_width, 10, 90)?;
+        config.layout.preview_width = layout.integer("preview-width", config.layout.preview_width, 10, 80)?;
+        config.layout.scroll_step = layout.integer("scroll-step", config.layout.scroll_step, 1, 1000)?;
+
+        let theme_section = Section::of(&table, "theme", &["name", "colors", "file-colors"])?;
+        if let Some(name) = theme_section.string("name")? {
+            config.theme = Theme::builtin(name).ok_or_else(|| format!(
+                "theme.name: unknown theme '{}'; expected one of {}", name, theme::THEMES.join(", ")))?;
+        }
+        if let Some(depth) = theme_section.string("colors")? {
+            config.color_depth = Depth::from_name(depth).ok_or_else(|| format!(
+                "theme.colors: unknown setting '{}'; expected one of {}", depth, Depth::NAMES.join(", ")))?;
+        }
+        if !theme_section.boolean("file-colors", true)? {
+            config.file_colors = None;
+        }
+
+        let roles: Vec<_> = theme::ROLES.iter().map(|(name, _)| *name).collect();
+        let colors = Section::of(&table, "colors", &roles)?;
+        for (name, role) in theme::ROLES {
+            if let Some(spec) = colors.string(name)? {
+                let style = theme::parse_style(spec).map_err(|message| format!("colors.{}: {}", name, message))?;
+                config.theme.set(*role, style);
+            }
+        }
+
+        let format = Section::of(&table, "format", &["date", "size"])?;
+        if let Some(date) = format.string("date")? {
+            if StrftimeItems::new(date).any(|item| item == Item::Error) {
+                return Err(format!("format.date: invalid date format '{}'", date));
+            }
+            config.date_format = date.to_string();
+            // a late date with a long month name gives about the widest a date can be
+            let widest = Utc.with_ymd_and_hms(2000, 9, 30, 23, 59, 59).unwrap();
+            config.date_width = widest.format(date).to_string().chars().count().max("Last Modified".len());
+        }
+        config.size_format = match format.string("size")? {
+            None => config.size_format,
+            Some("binary") => SizeFormat::Binary,
+            Some("decimal") => SizeFormat::Decimal,
+            Some("bytes") => SizeFormat::Bytes,
+            Some(size) => return Err(format!("format.size: unknown size format '{}'; expected one of binary, decimal, bytes", size)),
+        };
+
+        let listing = Section::of(&table, "listing", &["sort", "descending", "natural", "ignore-case", "show-hidden", "columns"])?;
+        if let Some(key) = listing.string("sort")? {
+            config.sort.key = SortKey::from_name(key).ok_or_else(|| format!(
+                "listing.sort: unknown sort '{}'; expected one of {}", key, SortKey::NAMES.join(", ")))?;
+        }
+        config.sort.descending = listing.boolean("descending", config.sort.descending)?;
+        config.sort.natural = listing.boolean("natural", config.sort.natural)?;
+        config.sort.ignore_case = listing.boolean("ignore-case", config.sort.ignore_case)?;
+        config.show_hidden = listing.boolean("show-hidden", config.show_hidden)?;
+        if let Some(columns) = listing.strings("columns")? {
+            config.columns = columns.into_iter()
+                .map(|name| Column::from_name(name).ok_or_else(|| format!(
+                    "listing.columns: unknown column '{}'; expected any of {}", name, Column::NAMES.join(", "))))
+                .collect::<Result<_, _>>()?;
+        }
+
+        let confirm = Section::of(&table, "confirm", &["permanent-remove", "pattern", "rename-batch", "empty-trash"])?;
+        config.confirm.permanent_remove = confirm.boolean("permanent-remove", config.confirm.permanent_remove)?;
+        config.confirm.pattern = confirm.boolean("pattern", config.confirm.pattern)?;
+        config.confirm.rename_batch = confirm.boolean("rename-batch", config.confirm.rename_batch)?;
+        config.confirm.empty_trash = confirm.boolean("empty-trash", config.confirm.empty_trash)?;
+
+        config.keymap = Keymap::from_config(table.get("keys"))?;
+        Ok(config)
+    }
+
+    // makes the theme the one text is drawn with; --no-color turns colors off whatever the
+    // configuration says
+    pub fn apply_theme(&self, no_color_flag: bool) {
+        let depth = if no_color_flag { Depth::None } else { self.color_depth.unwrap_or_else(Depth::detect) };
+        theme::set_active(self.theme.clone(), depth);
+    }
+
+    // the color of an entry's name by its kind and extension (see FileColors::color)
+    pub fn file_color(&self, name: &OsStr, is_link: bool, meta: Option<&Metadata>) -> Option<Color> {
+        self.file_colors.as_ref().and_then(|colors| colors.color(name, is_link, meta))
+    }
+
+    // formats a timestamp as shown in the contents panel; blank if the platform does not provide it
+    pub fn format_date(&self, time: Option<SystemTime>) -> String {
+        let date = time.map(|time| DateTime::<Utc>::from(time).format(&self.date_format).to_string());
+        format!("{:width$}", date.unwrap_or_default(), width = self.date_width)
+    }
+
+    // the headings of the date and size columns, and their underlines
+    pub fn date_size_headings(&self) -> (String, String) {
+        (format!("{:width$}  {:>7}", "Last Modified", "Size", width = self.date_width),
+            format!("{:width$}  {:>7}", "‾‾‾‾‾‾‾‾‾‾‾‾‾", "‾‾‾‾", width = self.date_width))
+    }
+
+    pub fn format_size(&self, size: u64) -> String {
+        const DECIMAL_UNITS: &[(u64, &str)] = &[(1_000_000_000, "GB"), (1_000_000, "MB"), (1_000, "kB")];
+        match self.size_format {
+            SizeFormat::Binary => crate::file_size_to_str(size),
+            SizeFormat::Decimal => match DECIMAL_UNITS.iter().find(|(unit, _)| size >= *unit) {
+                Some((unit, name)) => format!("{} {}", size / unit, name),
+                None => format!("{} B", size),
+            },
+            SizeFormat::Bytes => format!("{} B", size),
+        }
+    }
+
+    // the column of the line between the tree and the contents, and of the line between the
+    // contents and the preview pane if it is open; the widths are moved as little as needed to
+    // keep every panel at least MIN_PANEL_WIDTH wide
+    pub fn divider_positions(&self, width: usize, preview_pane: bool) -> (usize, Option<usize>) {
+        let place = |x: usize, min: usize, max: usize| x.min(max).max(min);
+        let last_line_x = width.saturating_sub(MIN_PANEL_WIDTH + 1);
+        if preview_pane {
+            let preview_line_x = place(width * (100 - self.layout.preview_width) / 100, 2 * MIN_PANEL_WIDTH + 1, last_line_x);
+            let line_x = place(preview_line_x * self.layout.tree_width / 100, MIN_PANEL_WIDTH, preview_line_x - MIN_PANEL_WIDTH - 1);
+            (line_x, Some(preview_line_x))
+        } else {
+            (place(width * self.layout.tree_width / 100, MIN_PANEL_WIDTH, last_line_x), None)
+        }
+    }
+
+    // whether a terminal of this size has room for every panel, with the preview pane open
+    pub fn window_fits(&self, width: usize, height: usize) -> bool {
+        let (line_x, preview_line_x) = self.divider_positions(width, true);
+        let preview_line_x = preview_line_x.unwrap();
+        width >= MIN_WINDOW_WIDTH && height >= MIN_WINDOW_HEIGHT
+            && line_x >= MIN_PANEL_WIDTH
+            && preview_line_x - line_x > MIN_PANEL_WIDTH
+            && width - preview_line_x > MIN_PANEL_WIDTH
+    }
+}
+
+impl<'a> StateManager<'a> {
+    // reload-config
+    pub fn reload_config(&mut self) -> io::Result<()> {
+        let config = match Config::load(self.config.file.as_deref()) {
+            Ok(config) => config,
+            // the configuration in use is kept
+            Err(message) => return self.print_error(&message),
+        };
+        let (height, width) = self.term.size();
+        if !config.window_fits(width as usize, height as usize) {
+            return self.print_error("The terminal window is too small for this configuration");
+        }
+
+        // hidden entries are left out when directories are read, so the tree is read again
+        let show_hidden = self.show_hidden_flag || config.show_hidden;
+        self.config = config;
+        self.sort = self.config.sort;
+        self.config.apply_theme(self.no_color_flag);
+        self.layout();
+        crate::draw_outline(self, CurrentArea::Command)?;
+        if show_hidden != self.show_hidden {
+            self.show_hidden = show_hidden;
+            self.scanner = Scanner::start(show_hidden);
+            self.pending_scans.clear();
+            self.reload_tree()?;
+        } else {
+            self.refresh_area(true, true)?;
+        }
+        self.print_message("Configuration reloaded")
+    }
+}
+
+// a section of the configuration file (which may be missing)
+struct Section<'t> {
+    name: &'static str,
+    table: Option<&'t toml::Table>,
+}
+
+impl<'t> Section<'t> {
+    // the section of the given name; anything but the known keys is an error
+    fn of(table: &'t toml::Table, name: &'static str, keys: &[&str]) -> Result<Self, String> {
+        let section = match table.get(name) {
+            Some(toml::Value::Table(section)) => section,
+            Some(_) => return Err(format!("[{}] must be a table", name)),
+            None => return Ok(Self { name, table: None }),
+        };
+        if let Some(key) = section.keys().find(|key| !keys.contains(&key.as_str())) {
+            return Err(format!("unknown setting '{}' in [{}]; expected one of {}", key, name, keys.join(", ")));
+        }
+        Ok(Self { name, table: Some(section) })
+    }
+
+    fn get(&self, key: &str) -> Option<&'t toml::Value> {
+        self.table.and_then(|table| table.get(key))
+    }
+
+    fn string(&self, key: &str) -> Result<Option<&'t str>, String> {
+        match self.get(key) {
+            Some(value) => value.as_str().map(Some).ok_or_else(|| format!("{}.{} must be a string", self.name, key)),
+            None => Ok(None),
+        }
+    }
+
+    fn strings(&self, key: &str) -> Result<Option<Vec<&'t str>>, String> {
+        match self.get(key) {
+            Some(value) => value.as_array()
+                .and_then(|values| values.iter().map(toml::Value::as_str).collect::<Option<Vec<_>>>())
+                .map(Some)
+                .ok_or_else(|| format!("{}.{} must be a list of strings", self.name, key)),
+            None => Ok(None),
+        }
+    }
+
+    fn boolean(&self, key: &str, default: bool) -> Result<bool, String> {
+        match self.get(key) {
+            Some(value) => value.as_bool().ok_or_else(|| format!("{}.{} must be true or false", self.name, key)),
+            None => Ok(default),
+        }
+    }
+
+    fn integer(&self, key: &str, default: usize, min: usize, max: usize) -> Result<usize, String> {
+        match self.get(key) {
+            Some(value) => value.as_integer()
+                .filter(|n| (min as i64..=max as i64).contains(n))
+                .map(|n| n as usize)
+                .ok_or_else(|| format!("{}.{} must be a number from {} to {}", self.name, key, min, max)),
+            None => Ok(default),
+        }
+    }
+}
+
+// config.toml in the configuration directory, if there is one
+fn default_path() -> Option<PathBuf> {
+    platform::config_dir()
+        .map(|dir| dir.join("config.toml"))
+        .filter(|path| path.is_file())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Config {
+        Config::parse(text).unwrap_or_else(|message| panic!("{}", message))
+    }
+
+    fn error(text: &str) -> String {
+        match Config::parse(text) {
+            Ok(_) => panic!("'{}' was accepted", text),
+            Err(message) => message,
+        }
+    }
+
+    #[test]
+    fn settings() {
+        let config = parse("[layout]\ntree-width = 30\n[listing]\nsort = \"size\"\ncolumns = []\n[format]\ndate = \"%Y-%m-%d\"");
+        assert_eq!(config.layout.tree_width, 30);
+        assert_eq!(config.layout.preview_width, 33);
+        assert!(config.sort.key == SortKey::Size);
+        assert!(config.columns.is_empty());
+        // never narrower than the heading
+        assert_eq!(config.date_width, "Last Modified".len());
+        assert_eq!(parse("[format]\ndate = \"%A %B %d %Y %H:%M:%S\"").date_width, "Saturday September 30 2000 23:59:59".len());
+    }
+
+    #[test]
+    fn unknown_sections_and_keys() {
+        assert_eq!(error("[layuot]\ntree-width = 30"),
+            "unknown section [layuot]; expected one of layout, theme, colors, format, listing, confirm, keys");
+        assert_eq!(error("[layout]\ntree = 30"),
+            "unknown setting 'tree' in [layout]; expected one of tree-width, preview-width, scroll-step");
+        assert!(error("[colors]\nbackground = \"red\"").starts_with("unknown setting 'background' in [colors]"));
+        assert!(error("[colors]\nerror = \"pink\"").starts_with("colors.error: "));
+        assert_eq!(error("layout = 3"), "[layout] must be a table");
+        assert!(error("[listing]\nsort = \"colour\"").starts_with("listing.sort: unknown sort 'colour'"));
+        assert!(error("[listing]\ncolumns = [\"mtime\"]").starts_with("listing.columns: unknown column 'mtime'"));
+        assert_eq!(error("[listing]\ncolumns = \"size\""), "listing.columns must be a list of strings");
+    }
+
+    #[test]
+    fn integers_out_of_range() {
+        assert_eq!(error("[layout]\ntree-width = 9"), "layout.tree-width must be a number from 10 to 90");
+        assert_eq!(error("[layout]\ntree-width = 91"), "layout.tree-width must be a number from 10 to 90");
+        assert_eq!(error("[layout]\npreview-width = 81"), "layout.preview-width must be a number from 10 to 80");
+        assert_eq!(error("[layout]\nscroll-step = 0"), "layout.scroll-step must be a number from 1 to 1000");
+        assert_eq!(error("[layout]\nscroll-step = -1"), "layout.scroll-step must be a number from 1 to 1000");
+        assert_eq!(error("[layout]\nscroll-step = 5.5"), "layout.scroll-step must be a number from 1 to 1000");
+        assert_eq!(parse("[layout]\ntree-width = 10\npreview-width = 80").layout.tree_width, 10);
+    }
+
+    #[test]
+    fn other_errors() {
+        assert_eq!(error("[confirm]\npattern = \"yes\""), "confirm.pattern must be true or false");
+        assert!(error("[format]\ndate = \"%Q\"").starts_with("format.date: invalid date format"));
+        assert!(error("[layout]\n\ntree-width = ").starts_with("line 3: "));
+    }
+
+    #[test]
+    fn panels_are_never_too_narrow() {
+        let mut config = Config::default();
+        for tree_width in [10, 50, 90] {
+            for preview_width in [10, 33, 80] {
+                config.layout.tree_width = tree_width;
+                config.layout.preview_width = preview_width;
+                for width in MIN_WINDOW_WIDTH..400 {
+                    assert!(config.window_fits(width, MIN_WINDOW_HEIGHT));
+                    let (line_x, _) = config.divider_positions(width, false);
+                    assert!(line_x >= MIN_PANEL_WIDTH && width - line_x > MIN_PANEL_WIDTH);
+                }
+            }
+        }
+
+        config.layout.tree_width = 10;
+        config.layout.preview_width = 80;
+        assert_eq!(config.divider_positions(199, true), (MIN_PANEL_WIDTH, Some(39)));
+        config.layout.tree_width = 90;
+        assert_eq!(config.divider_positions(100, true), (15, Some(20)));
+        assert!(!config.window_fits(MIN_WINDOW_WIDTH - 1, MIN_WINDOW_HEIGHT));
+    }
+}
@@ -0,0 +1,163 @@
+// running other programs on the files in the tree: an editor, a pager, any program, or a shell
+//
+// the program gets the whole terminal while it runs, with the current directory as its working
+// directory; once it exits the screen is drawn again and the tree is read again, as the program
+// may have changed anything in it
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+use crossterm::terminal;
+use crate::{command, names, platform, scan};
+use crate::{CurrentArea, DirectoryRef, StateManager};
+
+impl<'a> StateManager<'a> {
+    // edit <file|#N>
+    pub fn edit(&mut self, arg: &OsStr) -> io::Result<()> {
+        // the file does not have to exist yet, the editor can create it
+        let path = match self.program_argument(arg, true)? {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let mut command = program_from_env(&["VISUAL", "EDITOR"], platform::DEFAULT_EDITOR);
+        command.push(path.into_os_string());
+        self.run_program(command, true)
+    }
+
+    // page <file|#N>
+    pub fn page(&mut self, arg: &OsStr) -> io::Result<()> {
+        let path = match self.program_argument(arg, false)? {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let mut command = program_from_env(&["PAGER"], platform::DEFAULT_PAGER);
+        command.push(path.into_os_string());
+        self.run_program(command, true)
+    }
+
+    // open-with <program> <file|#N>
+    pub fn open_with(&mut self, program: &OsStr, arg: &OsStr) -> io::Result<()> {
+        let path = match self.program_argument(arg, false)? {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        self.run_program(vec![program.to_os_string(), path.into_os_string()], true)
+    }
+
+    // shell
+    pub fn shell(&mut self) -> io::Result<()> {
+        // the exit status of a shell is that of the last command run in it, so it is not reported
+        self.run_program(vec![platform::shell()], false)
+    }
+
+    // the path of a file to hand to a program: a search result or a name in the current
+    // directory; prints an error and returns None if there is no such file
+    fn program_argument(&mut self, arg: &OsStr, may_be_new: bool) -> io::Result<Option<PathBuf>> {
+        match self.find_result(arg) {
+            Some(Ok(path)) => Ok(Some(path)),
+            Some(Err(message)) => {
+                self.print_error(&message)?;
+                Ok(None)
+            },
+            None if !crate::is_valid_name(arg) => {
+                self.print_error("The file must be in the current directory, or a search result such as #1")?;
+                Ok(None)
+            },
+            None => {
+                let path = self.curr_dir.borrow().full_path.join(arg);
+                if !may_be_new && path.symlink_metadata().is_err() {
+                    self.print_error(&format!("'{}' does not exist", names::display_name(arg)))?;
+                    return Ok(None);
+                }
+                Ok(Some(path))
+            },
+        }
+    }
+
+    // hands the terminal over to a program (the first element of command) until it exits
+    fn run_program(&mut self, command: Vec<OsString>, report_status: bool) -> io::Result<()> {
+        let program = names::display_name(&command[0]);
+        let dir = self.curr_dir.borrow().full_path.clone();
+
+        self.term.clear_screen()?;
+        self.term.show_cursor()?;
+        terminal::disable_raw_mode().map_err(io::Error::other)?;
+        let status = Command::new(&command[0]).args(&command[1..]).current_dir(&dir).status();
+        terminal::enable_raw_mode().map_err(io::Error::other)?;
+
+        self.redraw_screen()?;
+        self.reload_tree()?;
+        match status {
+            Ok(status) if report_status && !status.success() => match status.code() {
+                Some(code) => self.print_error(&format!("'{}' exited with status {}", program, code)),
+                None => self.print_error(&format!("'{}' was stopped by a signal", program)),
+            },
+            Ok(_) => Ok(()),
+            Err(error) => self.print_error(&format!("Could not run '{}': {}", program, scan::describe_error(&error))),
+        }
+    }
+
+    // draws everything again after another program has had the terminal (whose size may
+    // have changed in the meantime)
+    fn redraw_screen(&mut self) -> io::Result<()> {
+        let height = self.term.size().0 as usize;
+        self.term.clear_screen()?;
+        self.layout();
+        crate::draw_outline(self, CurrentArea::Command)?;
+        self.term.move_cursor_to(0, height - 1)?;
+        self.term.clear_line()?;
+        self.term.write_str(" > ")
+    }
+
+    // reads the tree again after something outside of the program may have changed it; the
+    // directories from the root down to the current directory are read right away and the rest
+    // is read again in the background (or when it is next opened)
+    pub fn reload_tree(&mut self) -> io::Result<()> {
+        let mut path_to_curr_dir = vec![];
+        let mut dir = Some(self.curr_dir.clone());
+        while let Some(next) = dir {
+            dir = next.borrow().parent.clone();
+            path_to_curr_dir.push(next);
+        }
+
+        mark_unloaded(&self.root);
+        for dir in path_to_curr_dir.iter().rev() {
+            // the current directory (or one above it) may have been removed
+            if !self.is_in_tree(dir) {
+                break;
+            }
+            self.ensure_loaded(dir)?;
+        }
+        self.forget_detached_dirs();
+        self.refresh_area(true, true)
+    }
+}
+
+// marks every loaded directory in a subtree to be read again; what was read before stays in
+// the tree until then
+fn mark_unloaded(dir: &DirectoryRef) {
+    let mut dir = dir.borrow_mut();
+    if dir.loaded {
+        dir.loaded = false;
+        for child in &dir.directories {
+            mark_unloaded(child);
+        }
+    }
+}
+
+// the program named by the first set environment variable (which may include arguments, e.g.
+// 'code --wait'), or a default
+fn program_from_env(vars: &[&str], default: &str) -> Vec<OsString> {
+    for var in vars {
+        let value = match env::var(var) {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+        match command::tokenize(&value) {
+            Ok(tokens) if !tokens.is_empty() => return tokens,
+            _ => continue,
+        }
+    }
+    vec![OsString::from(default)]
+}
@@ -0,0 +1,109 @@
+// colors of file names by the kind of file and by extension, read from the LS_COLORS
+// environment variable in the format used by GNU ls (and dircolors), e.g.
+//
+//   di=01;34:ln=01;36:ex=01;32:*.tar=01;31
+//
+// entries are a two letter code or a '*' followed by a file name ending, then '=' and the
+// parameters of an SGR escape sequence; endings are matched regardless of case, and the
+// longest ending which matches is used
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsStr;
+use std::fs::Metadata;
+use crate::platform::{FileKind, PlatformMetadata};
+use crate::theme::{self, Color, Style};
+
+// used when LS_COLORS is not set; close to the defaults of GNU dircolors
+const DEFAULT_LS_COLORS: &str = concat!(
+    "di=01;34:ln=01;36:or=40;31;01:pi=40;33:so=01;35:bd=40;33;01:cd=40;33;01:su=37;41:sg=30;43:ex=01;32:",
+    // archives
+    "*.tar=01;31:*.tgz=01;31:*.gz=01;31:*.bz2=01;31:*.xz=01;31:*.zst=01;31:*.lz=01;31:*.lzma=01;31:*.zip=01;31:",
+    "*.7z=01;31:*.rar=01;31:*.jar=01;31:*.deb=01;31:*.rpm=01;31:*.cpio=01;31:*.iso=01;31:",
+    // images and video
+    "*.jpg=01;35:*.jpeg=01;35:*.png=01;35:*.gif=01;35:*.bmp=01;35:*.svg=01;35:*.webp=01;35:*.tif=01;35:",
+    "*.tiff=01;35:*.ico=01;35:*.mp4=01;35:*.mkv=01;35:*.webm=01;35:*.avi=01;35:*.mov=01;35:",
+    // audio
+    "*.mp3=00;36:*.flac=00;36:*.ogg=00;36:*.opus=00;36:*.wav=00;36:*.m4a=00;36",
+);
+
+pub struct FileColors {
+    // by two letter code, e.g. 'di' for directories
+    kinds: HashMap<String, Style>,
+    // lowercase file name endings, e.g. '.tar.gz'
+    endings: Vec<(String, Style)>,
+    // whether symbolic links are colored as what they point to ('ln=target')
+    link_as_target: bool,
+}
+
+impl FileColors {
+    // the colors from LS_COLORS if it is set, or the built-in ones otherwise
+    pub fn from_env() -> Self {
+        match env::var("LS_COLORS") {
+            Ok(value) if !value.is_empty() => Self::parse(&value),
+            _ => Self::parse(DEFAULT_LS_COLORS),
+        }
+    }
+
+    // entries which cannot be understood are left out, as ls does
+    fn parse(value: &str) -> Self {
+        let mut colors = Self { kinds: HashMap::new(), endings: vec![], link_as_target: false };
+        for entry in value.split(':') {
+            let (key, codes) = match entry.split_once('=') {
+                Some(entry) => entry,
+                None => continue,
+            };
+            if key == "ln" && codes == "target" {
+                colors.link_as_target = true;
+                continue;
+            }
+            let style = match theme::parse_sgr(codes) {
+                Some(style) => style,
+                None => continue,
+            };
+            match key.strip_prefix('*') {
+                Some(ending) => colors.endings.push((ending.to_lowercase(), style)),
+                None => { colors.kinds.insert(key.to_string(), style); },
+            }
+        }
+        colors
+    }
+
+    // the color of an entry's name; `meta` is that of the entry, or of its target if it is a
+    // symbolic link (or of the link itself if the link is broken)
+    pub fn color(&self, name: &OsStr, is_link: bool, meta: Option<&Metadata>) -> Option<Color> {
+        let kind = meta.map(|meta| meta.file_kind());
+        if is_link {
+            if matches!(kind, None | Some(FileKind::Link)) {
+                return self.kind("or").or_else(|| self.kind("ln"));
+            }
+            if !self.link_as_target {
+                return self.kind("ln");
+            }
+        }
+        let code = match kind {
+            None | Some(FileKind::Link) => return self.kind("fi"),
+            Some(FileKind::Regular) => return self.ending(name).or_else(|| self.kind("fi")),
+            Some(FileKind::Directory) => "di",
+            Some(FileKind::Executable) => "ex",
+            Some(FileKind::Setuid) => "su",
+            Some(FileKind::Setgid) => "sg",
+            Some(FileKind::Fifo) => "pi",
+            Some(FileKind::Socket) => "so",
+            Some(FileKind::BlockDevice) => "bd",
+            Some(FileKind::CharDevice) => "cd",
+        };
+        self.kind(code).or_else(|| self.kind("fi"))
+    }
+
+    fn kind(&self, code: &str) -> Option<Color> {
+        self.kinds.get(code).map(|style| Color::Custom(*style))
+    }
+
+    fn ending(&self, name: &OsStr) -> Option<Color> {
+        let name = name.to_string_lossy().to_lowercase();
+        self.endings.iter()
+            .filter(|(ending, _)| name.ends_with(ending.as_str()))
+            .max_by_key(|(ending, _)| ending.len())
+            .map(|(_, style)| Color::Custom(*style))
+    }
+}
@@ -0,0 +1,515 @@
+// searching the current directory and everything below it for files and directories
+//
+// 'find <pattern> [--type file|dir|link] [--size +N|-N] [--age +N|-N]' lists every entry whose
+// name matches the pattern (see the pattern module) and which passes the filters; the results
+// (of 'find', or of 'grep' in the grep module) are numbered so that other commands can refer to
+// them as '#N'. both walk the tree on a background thread, and the results are shown once the
+// search has finished
+use std::ffi::{OsStr, OsString};
+use std::fs::Metadata;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use crate::theme::Color;
+use crate::{names, scan};
+use crate::pattern::{self, NameMatcher};
+use crate::platform::PlatformMetadata;
+use crate::scan::ScannedEntry;
+use crate::{ColoredString, CommandProcedure, ContentsView, DirQuery, StateManager, Vector2};
+use crate::marks;
+
+enum EntryType {
+    File,
+    Directory,
+    Link,
+}
+
+// the filters of a search; sizes are in bytes
+struct Filters {
+    entry_type: Option<EntryType>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    // modified at least this long ago
+    min_age: Option<Duration>,
+    // modified at most this long ago
+    max_age: Option<Duration>,
+}
+
+pub struct FindResult {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub meta: Option<Metadata>,
+    // the lines of the file which matched, for results of 'grep'
+    pub lines: Vec<MatchedLine>,
+}
+
+pub struct MatchedLine {
+    // starting from 1
+    pub number: usize,
+    pub text: String,
+    // the byte range of the match within text
+    pub matched: Range<usize>,
+}
+
+// the results of the last search
+pub struct FindResults {
+    // the directory which was searched
+    pub base: PathBuf,
+    // the search command, as typed
+    pub query: String,
+    pub entries: Vec<FindResult>,
+}
+
+// a search running on a background thread, so that walking the tree (and reading files, for
+// 'grep') does not hold up the interface; dropping it stops the search
+pub struct RunningSearch {
+    // the directory being searched
+    base: PathBuf,
+    // the search command, as typed
+    query: String,
+    worker: Worker,
+}
+
+// what a finished search found
+pub struct Finished {
+    pub entries: Vec<FindResult>,
+    // shown as an error if nothing was found
+    pub message: String,
+}
+
+// handle to the thread of a search
+struct Worker {
+    results: Receiver<Finished>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl RunningSearch {
+    // runs a search, which is given a flag to check for whether it should stop and returns
+    // None if it did
+    pub fn start<F>(base: PathBuf, query: String, search: F) -> Self
+    where
+        F: FnOnce(&AtomicBool) -> Option<Finished> + Send + 'static,
+    {
+        let (result_tx, result_rx) = mpsc::channel();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stopped_flag = stopped.clone();
+        thread::spawn(move || {
+            if let Some(finished) = search(&stopped_flag) {
+                // the receiver is gone if the search was dropped in the meantime
+                let _ = result_tx.send(finished);
+            }
+        });
+        Self { base, query, worker: Worker { results: result_rx, stopped } }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.stopped.store(true, atomic::Ordering::Relaxed);
+    }
+}
+
+// the files and the directories in a directory, sorted the same way as in the tree; nothing if
+// the directory cannot be read, since it is shown as such in the tree
+pub fn read_sorted(path: &Path, show_hidden: bool) -> (Vec<ScannedEntry>, Vec<ScannedEntry>) {
+    let mut entries = scan::read_entries(path, show_hidden).unwrap_or_default();
+    entries.sort_by(|x, y| x.path.file_name().cmp(&y.path.file_name()));
+    let (directories, files) = entries.into_iter().partition(|entry| entry.is_dir);
+    (files, directories)
+}
+
+// the entries below a directory which match, in the same order as they are listed in the tree;
+// None if the search was stopped
+fn find_in_tree(base: &Path, show_hidden: bool, matcher: &NameMatcher, filters: &Filters, stopped: &AtomicBool) -> Option<Vec<FindResult>> {
+    let mut found = vec![];
+    let mut dirs = vec![base.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        if stopped.load(atomic::Ordering::Relaxed) {
+            return None;
+        }
+        let (files, directories) = read_sorted(&dir, show_hidden);
+        for entry in files.iter().chain(&directories) {
+            if matcher.is_match(entry.path.file_name().unwrap()) && filters.accept(&entry.path, entry.is_dir, entry.meta.as_ref()) {
+                found.push(FindResult { path: entry.path.clone(), is_dir: entry.is_dir, meta: entry.meta.clone(), lines: vec![] });
+            }
+        }
+        dirs.extend(directories.into_iter().rev().map(|entry| entry.path));
+    }
+    Some(found)
+}
+
+impl Filters {
+    // parses the options after the pattern; the error is a message for the user
+    fn parse(args: &[OsString]) -> Result<Self, String> {
+        let mut filters = Self { entry_type: None, min_size: None, max_size: None, min_age: None, max_age: None };
+        let mut args = args.iter();
+        while let Some(option) = args.next() {
+            let value = args.next().and_then(|value| value.to_str());
+            match (option.to_str(), value) {
+                (Some("--type"), Some(value)) => filters.entry_type = Some(match value {
+                    "file" => EntryType::File,
+                    "dir" => EntryType::Directory,
+                    "link" => EntryType::Link,
+                    _ => return Err(String::from("The type must be file, dir or link")),
+                }),
+                (Some("--size"), Some(value)) => {
+                    let (larger, amount) = split_sign(value)
+                        .ok_or("The size must be written as +N or -N, e.g. +1M or -10k")?;
+                    let size = parse_size(amount).ok_or("Sizes are a number followed by k, M or G, e.g. +1M")?;
+                    if larger { filters.min_size = Some(size) } else { filters.max_size = Some(size) }
+                },
+                (Some("--age"), Some(value)) => {
+                    let (older, amount) = split_sign(value)
+                        .ok_or("The age must be written as +N or -N, e.g. -7d or +1w")?;
+                    let age = parse_age(amount).ok_or("Ages are a number followed by s, m, h, d or w, e.g. -7d")?;
+                    if older { filters.min_age = Some(age) } else { filters.max_age = Some(age) }
+                },
+                (Some("--type"), None) | (Some("--size"), None) | (Some("--age"), None) => {
+                    return Err(format!("A value is missing after '{}'", option.to_string_lossy()));
+                },
+                _ => return Err(format!("Unknown option '{}'; use --type, --size or --age", option.to_string_lossy())),
+            }
+        }
+        Ok(filters)
+    }
+
+    fn accept(&self, path: &Path, is_dir: bool, meta: Option<&Metadata>) -> bool {
+        match self.entry_type {
+            Some(EntryType::File) if is_dir => return false,
+            Some(EntryType::Directory) if !is_dir => return false,
+            // symbolic links are shown as files, even when they point to directories
+            Some(EntryType::Link) if !path.symlink_metadata().is_ok_and(|m| m.file_type().is_symlink()) => return false,
+            _ => {},
+        }
+
+        if self.min_size.is_some() || self.max_size.is_some() {
+            // directories do not have a size of their own
+            let size = match meta {
+                Some(meta) if !is_dir => meta.file_size(),
+                _ => return false,
+            };
+            if self.min_size.is_some_and(|min| size <= min) || self.max_size.is_some_and(|max| size >= max) {
+                return false;
+            }
+        }
+
+        if self.min_age.is_some() || self.max_age.is_some() {
+            let age = match meta.and_then(|m| m.timestamps().modified) {
+                Some(modified) => SystemTime::now().duration_since(modified).unwrap_or_default(),
+                None => return false,
+            };
+            if self.min_age.is_some_and(|min| age <= min) || self.max_age.is_some_and(|max| age >= max) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// splits '+N' into (true, "N") and '-N' into (false, "N")
+fn split_sign(value: &str) -> Option<(bool, &str)> {
+    match value.strip_prefix('+') {
+        Some(amount) => Some((true, amount)),
+        None => value.strip_prefix('-').map(|amount| (false, amount)),
+    }
+}
+
+// parses a size such as '10k' or '1M' (in powers of 1024, the same as the contents panel)
+pub fn parse_size(text: &str) -> Option<u64> {
+    let (number, unit) = text.split_at(text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len()));
+    let multiplier = match unit {
+        "" => 1,
+        "k" | "K" => 1024,
+        "M" => 1024 * 1024,
+        "G" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+// parses an age such as '30m' or '7d'
+fn parse_age(text: &str) -> Option<Duration> {
+    let (number, unit) = text.split_at(text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len()));
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(Duration::from_secs(number.parse::<u64>().ok()?.checked_mul(seconds)?))
+}
+
+impl<'a> StateManager<'a> {
+    // find <pattern> [options]
+    pub fn find(&mut self, args: &[OsString]) -> io::Result<()> {
+        let matcher = match pattern::parse_name(&args[0]) {
+            Ok(matcher) => matcher,
+            Err(message) => return self.print_error(&message),
+        };
+        let filters = match Filters::parse(&args[1..]) {
+            Ok(filters) => filters,
+            Err(message) => return self.print_error(&message),
+        };
+
+        let base = self.curr_dir.borrow().full_path.clone();
+        let message = format!("Searching '{}'; enter 'find' to stop", names::display_name(base.as_os_str()));
+        let query = format!("find {}", args.iter().map(|arg| arg.to_string_lossy()).collect::<Vec<_>>().join(" "));
+        let pattern = query[5..].to_string();
+        let (dir, show_hidden) = (base.clone(), self.show_hidden);
+        // a search which is still running is stopped when it is replaced
+        self.search = Some(RunningSearch::start(base, query, move |stopped| {
+            let entries = find_in_tree(&dir, show_hidden, &matcher, &filters, stopped)?;
+            let message = if entries.is_empty() {
+                format!("Nothing in '{}' matches '{}'", names::display_name(dir.as_os_str()), pattern)
+            } else {
+                format!("Found {}; refer to a result as #N", marks::entry_count(entries.len()))
+            };
+            Some(Finished { entries, message })
+        }));
+        self.print_message(&message)
+    }
+
+    // find (or grep) while a search is running
+    pub fn stop_search(&mut self) -> io::Result<()> {
+        self.search = None;
+        self.print_message("Stopped the search")
+    }
+
+    // shows the results of the running search once it has finished; returns whether anything
+    // was drawn
+    pub fn apply_search_results(&mut self) -> io::Result<bool> {
+        // the results would take the place of a question waiting to be answered (and of what it
+        // is about), so they are held until it has been answered
+        let waiting = self.rename_plan.is_some() || self.confirmation.is_some() || self.dir_to_remove.is_some()
+            || self.command_buf.is_some() || self.contents_view == ContentsView::Selection;
+        if waiting {
+            return Ok(false);
+        }
+        let finished = match self.search.as_ref().map(|search| search.worker.results.try_recv()) {
+            Some(Ok(finished)) => finished,
+            Some(Err(TryRecvError::Empty)) | None => return Ok(false),
+            Some(Err(TryRecvError::Disconnected)) => {
+                self.search = None;
+                self.print_error("The search stopped unexpectedly")?;
+                return Ok(true);
+            },
+        };
+        let search = self.search.take().unwrap();
+
+        if finished.entries.is_empty() {
+            self.print_error(&finished.message)?;
+            return Ok(true);
+        }
+        self.find_results = Some(FindResults { base: search.base, query: search.query, entries: finished.entries });
+        self.show_find_results()?;
+        self.print_message(&finished.message)?;
+        Ok(true)
+    }
+
+    // switches the contents panel to the results of the last search
+    pub fn show_find_results(&mut self) -> io::Result<()> {
+        self.contents_view = ContentsView::FindResults;
+        self.contents_cursor = None;
+        self.dir_contents.curr_pos = Vector2 { x: 0, y: 0 };
+        self.refresh_area(false, true)
+    }
+
+    // the path of the result an argument refers to: '#N', or '#' for the highlighted one; None if
+    // the argument does not refer to a result, otherwise the error is a message for the user
+    pub fn find_result(&self, arg: &OsStr) -> Option<Result<PathBuf, String>> {
+        let reference = arg.to_str()?.strip_prefix('#')?;
+        let results = match &self.find_results {
+            Some(results) => results,
+            None => return Some(Err(String::from("There are no search results to refer to; use 'find' first"))),
+        };
+
+        let index = if reference.is_empty() {
+            match self.contents_cursor {
+                Some(cursor) if self.contents_view == ContentsView::FindResults => cursor,
+                _ => return Some(Err(String::from("No search result is highlighted"))),
+            }
+        } else {
+            match reference.parse::<usize>() {
+                Ok(number) if number >= 1 && number <= results.entries.len() => number - 1,
+                _ => return Some(Err(format!("There is no search result #{}", reference))),
+            }
+        };
+
+        let path = &results.entries[index].path;
+        if path.symlink_metadata().is_err() {
+            return Some(Err(format!("'{}' no longer exists", names::display_name(path.as_os_str()))));
+        }
+        Some(Ok(path.clone()))
+    }
+
+    // a directory argument of a command: a search result if it is given as '#N', otherwise a
+    // name to look up in the tree; None if a problem with the search result was reported
+    pub fn dir_argument(&mut self, arg: &OsString) -> io::Result<Option<DirQuery>> {
+        match self.find_result(arg) {
+            Some(Ok(path)) => match self.load_dir_path(&path)? {
+                Some(dir) => Ok(Some(DirQuery::Disambiguated(dir))),
+                None => {
+                    self.print_error(&format!("Search result {} is not a directory", arg.to_string_lossy()))?;
+                    Ok(None)
+                },
+            },
+            Some(Err(message)) => {
+                self.print_error(&message)?;
+                Ok(None)
+            },
+            None => Ok(Some(DirQuery::ByName(arg.clone()))),
+        }
+    }
+
+    // moves or copies a single search result, which may be anywhere below the directory that
+    // was searched, the same way as a batch of marked entries
+    pub fn use_find_result(&mut self, path: PathBuf, procedure: CommandProcedure<'a>, dir: DirQuery) -> io::Result<()> {
+        // the search did not add what it found to the tree
+        if let Some(parent) = path.parent() {
+            self.load_dir_path(parent)?;
+        }
+        self.selection = Some(vec![path]);
+        procedure(self, dir, OsStr::new(""))?;
+        if self.command_buf.is_none() {
+            self.clear_selection()?;
+        }
+        Ok(())
+    }
+
+    // enters a directory from the results, or the directory containing a file with the file highlighted
+    pub fn enter_find_result(&mut self, path: &Path) -> io::Result<()> {
+        let is_dir = self.load_dir_path(path)?.is_some();
+        let dir_path = if is_dir { path } else { path.parent().unwrap() };
+        let dir = match self.load_dir_path(dir_path)? {
+            Some(dir) => dir,
+            None => return self.print_error("The directory of this result is no longer in the tree"),
+        };
+
+        self.enter_dir(DirQuery::Disambiguated(dir), OsStr::new(""))?;
+        if !is_dir {
+            let index = self.curr_dir.borrow().files.iter().position(|f| f.borrow().full_path == path);
+            if let Some(index) = index {
+                self.set_contents_cursor(index)?;
+            }
+        }
+        Ok(())
+    }
+
+    // lists the results of the last search, relative to the directory which was searched
+    pub fn load_find_results(&self) -> Vec<Vec<ColoredString>> {
+        let results = match &self.find_results {
+            Some(results) => results,
+            None => return vec![vec![ColoredString::normal(String::from("Nothing has been searched for yet"))]],
+        };
+
+        let header = Color::Header;
+        let (heading, underline) = self.config.date_size_headings();
+        let mut contents = vec![
+            vec![ColoredString::colored(format!("Results of '{}' ({})", results.query, results.entries.len()), header)],
+            vec![ColoredString::colored(format!("    #  {}  Path", heading), header)],
+            vec![ColoredString::colored(format!("    ‾  {}  ‾‾‾‾", underline), header)],
+        ];
+        for (i, result) in results.entries.iter().enumerate() {
+            let cursor = if self.contents_cursor == Some(i) { '>' } else { ' ' };
+            let size = match &result.meta {
+                Some(_) if result.is_dir => String::new(),
+                Some(meta) => self.config.format_size(meta.file_size()),
+                None => String::from("?"),
+            };
+            let mut line = vec![ColoredString::normal(format!("{}{:>4}  {}  {:>7}  ",
+                cursor, format!("#{}", i + 1),
+                self.config.format_date(result.meta.as_ref().and_then(|m| m.timestamps().modified)), size))];
+
+            let relative = result.path.strip_prefix(&results.base).unwrap_or(&result.path);
+            // results which were moved or removed since the search are kept so that the numbers stay the same
+            match result.path.symlink_metadata() {
+                Err(_) => {
+                    line.extend(names::colored_name(relative.as_os_str(), Some(Color::Muted)));
+                    line.push(ColoredString::colored(String::from("  (no longer exists)"), Color::Muted));
+                },
+                Ok(link_meta) => {
+                    let name = result.path.file_name().unwrap_or(result.path.as_os_str());
+                    let color = self.config.file_color(name, link_meta.file_type().is_symlink(), result.meta.as_ref())
+                        .or(if result.is_dir { Some(Color::Directory) } else { None });
+                    line.extend(names::colored_name(relative.as_os_str(), color));
+                },
+            }
+            contents.push(line);
+
+            // matched lines are shown under the file, with the match highlighted
+            for matched in &result.lines {
+                let text = &matched.text;
+                contents.push(vec![
+                    ColoredString::colored(format!("{:>12}: ", matched.number), Color::Muted),
+                    ColoredString::normal(text[..matched.matched.start].to_string()),
+                    ColoredString::colored(text[matched.matched.clone()].to_string(), Color::Match),
+                    ColoredString::normal(text[matched.matched.end..].to_string()),
+                ]);
+            }
+        }
+        contents
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter_error(args: &[&str]) -> String {
+        let args: Vec<OsString> = args.iter().map(OsString::from).collect();
+        match Filters::parse(&args) {
+            Ok(_) => panic!("{:?} was accepted", args),
+            Err(message) => message,
+        }
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("0"), Some(0));
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("10k"), Some(10 * 1024));
+        assert_eq!(parse_size("10K"), Some(10 * 1024));
+        assert_eq!(parse_size("1M"), Some(1024 * 1024));
+        assert_eq!(parse_size("2G"), Some(2 * 1024 * 1024 * 1024));
+        for invalid in ["", "k", "1m", "1g", "1.5M", "1 M", "1MB", "M1", "-1", "+1", "99999999999G"] {
+            assert_eq!(parse_size(invalid), None, "'{}' was accepted", invalid);
+        }
+    }
+
+    #[test]
+    fn ages() {
+        assert_eq!(parse_age("30s"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_age("30m"), Some(Duration::from_secs(30 * 60)));
+        assert_eq!(parse_age("2h"), Some(Duration::from_secs(2 * 60 * 60)));
+        assert_eq!(parse_age("7d"), Some(Duration::from_secs(7 * 24 * 60 * 60)));
+        assert_eq!(parse_age("1w"), Some(Duration::from_secs(7 * 24 * 60 * 60)));
+        // an age always has a unit
+        for invalid in ["", "30", "d", "1y", "1D", "1.5d", "99999999999999999w"] {
+            assert_eq!(parse_age(invalid), None, "'{}' was accepted", invalid);
+        }
+    }
+
+    #[test]
+    fn signs() {
+        assert_eq!(split_sign("+1M"), Some((true, "1M")));
+        assert_eq!(split_sign("-7d"), Some((false, "7d")));
+        assert_eq!(split_sign("1M"), None);
+    }
+
+    #[test]
+    fn filter_errors() {
+        assert_eq!(filter_error(&["--size", "1M"]), "The size must be written as +N or -N, e.g. +1M or -10k");
+        assert_eq!(filter_error(&["--size", "+1MB"]), "Sizes are a number followed by k, M or G, e.g. +1M");
+        assert_eq!(filter_error(&["--age", "+7"]), "Ages are a number followed by s, m, h, d or w, e.g. -7d");
+        assert_eq!(filter_error(&["--type", "pipe"]), "The type must be file, dir or link");
+        assert_eq!(filter_error(&["--age"]), "A value is missing after '--age'");
+        assert_eq!(filter_error(&["--depth", "2"]), "Unknown option '--depth'; use --type, --size or --age");
+    }
+}
@@ -0,0 +1,59 @@
+// filesystem operations which are not provided by std directly
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// recursively copies a directory and everything in it; symbolic links are copied as links
+pub fn copy_dir_all(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let target = to.join(entry.file_name());
+
+        if file_type.is_symlink() {
+            copy_symlink(&entry.path(), &target)?;
+        } else if file_type.is_dir() {
+            copy_dir_all(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+// moves a file or directory; unlike fs::rename this also works across filesystems, in
+// which case the original is copied and then removed
+pub fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            let file_type = from.symlink_metadata()?.file_type();
+            if file_type.is_symlink() {
+                copy_symlink(from, to)?;
+                fs::remove_file(from)
+            } else if file_type.is_dir() {
+                copy_dir_all(from, to)?;
+                fs::remove_dir_all(from)
+            } else {
+                fs::copy(from, to)?;
+                fs::remove_file(from)
+            }
+        },
+        result => result,
+    }
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+// creating symbolic links requires extra privileges on windows, so copy what the link points to
+#[cfg(windows)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    if from.is_dir() {
+        copy_dir_all(from, to)
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
@@ -0,0 +1,228 @@
+// searching the contents of the files in the current directory and everything below it
+//
+// 'grep <expression> [--ignore-case] [--max-size N]' lists every file with a line matching a
+// regular expression, along with the lines; the files are numbered the same way as the results
+// of 'find', so other commands can refer to them as '#N'. the directories are walked and the
+// files read on a background thread, and the results are shown once the search has finished
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{self, AtomicBool};
+use regex::Regex;
+use crate::find::{self, Finished, FindResult, MatchedLine, RunningSearch};
+use crate::scan::ScannedEntry;
+use crate::{names, pattern};
+use crate::StateManager;
+
+// files larger than this are skipped unless --max-size is given
+const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+// the search stops after this many matched lines
+const MAX_MATCHED_LINES: usize = 1000;
+// a file is taken to be binary if a null byte appears this early in it
+pub const BINARY_CHECK_LEN: usize = 8000;
+// characters kept before and after a match in the shown lines
+const CONTEXT_BEFORE: usize = 40;
+const CONTEXT_AFTER: usize = 100;
+
+// counts of the files which were not searched
+#[derive(Default)]
+struct Skipped {
+    binary: usize,
+    too_large: usize,
+    unreadable: usize,
+    // FIFOs, sockets and devices, which reading could wait on or never finish
+    not_regular: usize,
+}
+
+impl<'a> StateManager<'a> {
+    // grep <expression> [options]
+    pub fn grep(&mut self, args: &[OsString]) -> io::Result<()> {
+        let expression = match args[0].to_str() {
+            Some(expression) => expression.to_string(),
+            None => return self.print_error("The expression must be valid unicode"),
+        };
+        let mut ignore_case = false;
+        let mut max_size = DEFAULT_MAX_SIZE;
+        let mut options = args[1..].iter();
+        while let Some(option) = options.next() {
+            match option.to_str() {
+                Some("--ignore-case") => ignore_case = true,
+                Some("--max-size") => match options.next().and_then(|value| value.to_str()).and_then(find::parse_size) {
+                    Some(size) => max_size = size,
+                    None => return self.print_error("--max-size must be followed by a size in bytes, or with k, M or G, e.g. 50M"),
+                },
+                _ => return self.print_error(&format!("Unknown option '{}'; use --ignore-case or --max-size",
+                    option.to_string_lossy())),
+            }
+        }
+        let regex = match pattern::build_regex(&expression, ignore_case) {
+            Ok(regex) => regex,
+            Err(message) => return self.print_error(&message),
+        };
+
+        let base = self.curr_dir.borrow().full_path.clone();
+        let message = format!("Searching '{}'; enter 'grep' to stop", names::display_name(base.as_os_str()));
+        let query = format!("grep {}", args.iter().map(|arg| arg.to_string_lossy()).collect::<Vec<_>>().join(" "));
+        let (dir, show_hidden) = (base.clone(), self.show_hidden);
+        // a search which is still running is stopped when it is replaced
+        self.search = Some(RunningSearch::start(base, query, move |stopped| {
+            let (entries, skipped, line_count) = search_tree(&dir, show_hidden, &regex, max_size, stopped)?;
+            let message = describe_results(&dir, &expression, max_size, &entries, &skipped, line_count);
+            Some(Finished { entries, message })
+        }));
+        self.print_message(&message)
+    }
+}
+
+// the message shown with the results of a search, or on its own if nothing was found
+fn describe_results(base: &Path, expression: &str, max_size: u64, entries: &[FindResult], skipped: &Skipped, line_count: usize) -> String {
+    let mut notes = vec![];
+    if skipped.binary > 0 {
+        notes.push(format!("{} binary", skipped.binary));
+    }
+    if skipped.too_large > 0 {
+        notes.push(format!("{} larger than {}", skipped.too_large, crate::file_size_to_str(max_size)));
+    }
+    if skipped.unreadable > 0 {
+        notes.push(format!("{} unreadable", skipped.unreadable));
+    }
+    if skipped.not_regular > 0 {
+        notes.push(format!("{} not regular files", skipped.not_regular));
+    }
+    let notes = if notes.is_empty() { String::new() } else { format!(" (skipped {})", notes.join(", ")) };
+
+    let file_count = entries.len();
+    if file_count == 0 {
+        format!("No file in '{}' contains '{}'{}", names::display_name(base.as_os_str()), expression, notes)
+    } else if line_count >= MAX_MATCHED_LINES {
+        format!("Stopped after {} in {}{}", plural(line_count, "line"), plural(file_count, "file"), notes)
+    } else {
+        format!("Found {} in {}{}; refer to a file as #N", plural(line_count, "line"), plural(file_count, "file"), notes)
+    }
+}
+
+// searches every file in a directory and everything below it, in the same order as they are
+// listed in the tree; returns the files with matches, the files skipped and the number of
+// matched lines, or None if the search was stopped
+fn search_tree(base: &Path, show_hidden: bool, regex: &Regex, max_size: u64, stopped: &AtomicBool) -> Option<(Vec<FindResult>, Skipped, usize)> {
+    let mut entries = vec![];
+    let mut skipped = Skipped::default();
+    let mut line_count = 0;
+    let mut dirs = vec![base.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        if stopped.load(atomic::Ordering::Relaxed) {
+            return None;
+        }
+        let (files, directories) = find::read_sorted(&dir, show_hidden);
+        dirs.extend(directories.into_iter().rev().map(|entry| entry.path));
+        for file in files {
+            if stopped.load(atomic::Ordering::Relaxed) {
+                return None;
+            }
+            if line_count >= MAX_MATCHED_LINES {
+                return Some((entries, skipped, line_count));
+            }
+            if let Some(entry) = search_file(file, regex, max_size, &mut skipped, &mut line_count) {
+                entries.push(entry);
+            }
+        }
+    }
+    Some((entries, skipped, line_count))
+}
+
+// searches a single file; returns it along with its matched lines if there are any, or counts
+// it as skipped if it could not be searched
+fn search_file(file: ScannedEntry, regex: &Regex, max_size: u64, skipped: &mut Skipped, line_count: &mut usize) -> Option<FindResult> {
+    let (path, meta) = match file {
+        ScannedEntry { path, meta: Some(meta), error: None, .. } => (path, meta),
+        _ => {
+            skipped.unreadable += 1;
+            return None;
+        },
+    };
+    // symbolic links are searched as what they point to
+    match fs::metadata(&path) {
+        Ok(target) if !target.is_file() => {
+            skipped.not_regular += 1;
+            return None;
+        },
+        Ok(target) if target.len() > max_size => {
+            skipped.too_large += 1;
+            return None;
+        },
+        Ok(_) => {},
+        Err(_) => {
+            skipped.unreadable += 1;
+            return None;
+        },
+    }
+    // no more than the limit is read, in case the file has grown since
+    let mut bytes = vec![];
+    if File::open(&path).and_then(|file| file.take(max_size.saturating_add(1)).read_to_end(&mut bytes)).is_err() {
+        skipped.unreadable += 1;
+        return None;
+    }
+    if bytes.len() as u64 > max_size {
+        skipped.too_large += 1;
+        return None;
+    }
+    if looks_binary(&bytes) {
+        skipped.binary += 1;
+        return None;
+    }
+
+    let text = String::from_utf8_lossy(&bytes);
+    let mut lines = vec![];
+    for (i, line) in text.lines().enumerate() {
+        if let Some(found) = regex.find(line) {
+            lines.push(shorten(line, found.range(), i + 1));
+            *line_count += 1;
+            if *line_count >= MAX_MATCHED_LINES {
+                break;
+            }
+        }
+    }
+    if lines.is_empty() {
+        return None;
+    }
+    Some(FindResult { path, is_dir: false, meta: Some(meta), lines })
+}
+
+// whether the contents of a file (or the start of them) look like binary data rather than text
+pub fn looks_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_CHECK_LEN)].contains(&0)
+}
+
+// the part of a matched line to show: leading whitespace is dropped, long lines are cut around
+// the match, and control characters (which would upset the terminal) are replaced
+fn shorten(line: &str, matched: Range<usize>, number: usize) -> MatchedLine {
+    let clean = |text: &str| text.chars().map(|c| if c.is_control() { ' ' } else { c }).collect::<String>();
+    let before = clean(&line[..matched.start]);
+    let found = clean(&line[matched.clone()]);
+    let after = clean(&line[matched.end..]);
+
+    let before = before.trim_start();
+    let before_len = before.chars().count();
+    let before = if before_len > CONTEXT_BEFORE {
+        format!("…{}", before.chars().skip(before_len - CONTEXT_BEFORE).collect::<String>())
+    } else {
+        before.to_string()
+    };
+    let after = after.trim_end();
+    let after = if after.chars().count() > CONTEXT_AFTER {
+        format!("{}…", after.chars().take(CONTEXT_AFTER).collect::<String>())
+    } else {
+        after.to_string()
+    };
+
+    let start = before.len();
+    let text = format!("{}{}{}", before, found, after);
+    MatchedLine { number, text, matched: start..start + found.len() }
+}
+
+// e.g. '1 line', '3 lines'
+fn plural(count: usize, noun: &str) -> String {
+    format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+}
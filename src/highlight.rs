@@ -0,0 +1,501 @@
+// syntax highlighting of source files shown in the preview
+//
+// each known language has a small hand-written lexer (so nothing has to be downloaded or
+// installed for it); the highlighting is done on a background thread and the plain text is
+// shown until it is finished, so that a long file does not hold up the interface
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use crate::theme::Color;
+use crate::ColoredString;
+
+const KEYWORD: Color = Color::Keyword;
+const STRING: Color = Color::StringLiteral;
+const NUMBER: Color = Color::Number;
+const COMMENT: Color = Color::Comment;
+const TYPE: Color = Color::Type;
+// keys, macros, attributes and links
+const NAME: Color = Color::Name;
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false", "fn", "for",
+    "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self", "static",
+    "struct", "super", "trait", "true", "type", "union", "unsafe", "use", "where", "while", "yield",
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Language {
+    Rust,
+    Toml,
+    Json,
+    Markdown,
+}
+
+impl Language {
+    // the language of a file, judged by its name
+    pub fn of(path: &Path) -> Option<Self> {
+        if path.file_name().is_some_and(|name| name == "Cargo.lock") {
+            return Some(Language::Toml);
+        }
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "rs" => Some(Language::Rust),
+            "toml" => Some(Language::Toml),
+            "json" => Some(Language::Json),
+            "md" | "markdown" => Some(Language::Markdown),
+            _ => None,
+        }
+    }
+}
+
+// lines to be highlighted; the id tells the finished lines apart from those of other previews
+struct Request {
+    id: u64,
+    language: Language,
+    lines: Vec<String>,
+}
+
+// the pieces of each line of a highlighted file
+pub struct Highlighted {
+    pub id: u64,
+    pub lines: Vec<Vec<ColoredString>>,
+}
+
+// handle to a worker thread which highlights files in the background
+pub struct Highlighter {
+    requests: Sender<Request>,
+    results: Receiver<Highlighted>,
+}
+
+impl Highlighter {
+    pub fn start() -> Self {
+        let (request_tx, request_rx) = mpsc::channel::<Request>();
+        let (result_tx, result_rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            // the thread finishes once the Highlighter (and with it the request sender) is dropped
+            while let Ok(mut request) = request_rx.recv() {
+                // only the latest preview is shown, so files skipped past are not highlighted
+                while let Ok(newer) = request_rx.try_recv() {
+                    request = newer;
+                }
+                let lines = highlight(request.language, &request.lines);
+                if result_tx.send(Highlighted { id: request.id, lines }).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            requests: request_tx,
+            results: result_rx,
+        }
+    }
+
+    // queues lines of a file to be highlighted
+    pub fn request(&self, id: u64, language: Language, lines: Vec<String>) {
+        // the worker only stops if the program is exiting, so a failed send can be ignored
+        let _ = self.requests.send(Request { id, language, lines });
+    }
+
+    // returns finished lines if there are any, without waiting
+    pub fn try_result(&self) -> Option<Highlighted> {
+        self.results.try_recv().ok()
+    }
+}
+
+// splits lines of a file into colored pieces
+fn highlight(language: Language, lines: &[String]) -> Vec<Vec<ColoredString>> {
+    let mut state = State::Normal;
+    lines.iter()
+        .map(|line| {
+            let mut line = Line::new(line);
+            let open = std::mem::replace(&mut state, State::Normal);
+            state = match language {
+                Language::Rust => rust_line(&mut line, open),
+                Language::Toml => toml_line(&mut line, open),
+                Language::Json => json_line(&mut line),
+                Language::Markdown => markdown_line(&mut line, open),
+            };
+            line.pieces
+        })
+        .collect()
+}
+
+// what is still open at the end of a line
+#[derive(Clone, PartialEq)]
+enum State {
+    Normal,
+    // a block comment, with how deeply comments are nested in it
+    BlockComment(usize),
+    // a string with backslash escapes
+    Str,
+    // a raw string, with how many '#' close it
+    RawStr(usize),
+    // a string closed by the given delimiter (which escapes are not checked for)
+    Delimited(&'static str),
+    // a fenced block of code closed by the given fence
+    Fence(String),
+}
+
+// a line being split into pieces; pieces next to each other with the same color are joined
+struct Line {
+    chars: Vec<char>,
+    pos: usize,
+    pieces: Vec<ColoredString>,
+}
+
+impl Line {
+    fn new(text: &str) -> Self {
+        Self { chars: text.chars().collect(), pos: 0, pieces: vec![] }
+    }
+
+    fn done(&self) -> bool {
+        self.pos >= self.chars.len()
+    }
+
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars().enumerate().all(|(i, c)| self.peek(i) == Some(c))
+    }
+
+    fn rest(&self) -> String {
+        self.chars[self.pos..].iter().collect()
+    }
+
+    // adds the characters up to an index to the line in a color
+    fn take_to(&mut self, end: usize, color: Option<Color>) {
+        let end = end.min(self.chars.len());
+        if end <= self.pos {
+            return;
+        }
+        let text: String = self.chars[self.pos..end].iter().collect();
+        self.pos = end;
+        match self.pieces.last_mut() {
+            Some(last) if last.color == color => last.string.push_str(&text),
+            _ => self.pieces.push(ColoredString { string: text, color }),
+        }
+    }
+
+    fn take(&mut self, len: usize, color: Option<Color>) {
+        self.take_to(self.pos + len, color);
+    }
+
+    // index just past the characters from the current position which satisfy a condition
+    fn scan_while(&self, from: usize, condition: impl Fn(char) -> bool) -> usize {
+        let mut end = self.pos + from;
+        while self.chars.get(end).is_some_and(|&c| condition(c)) {
+            end += 1;
+        }
+        end
+    }
+
+    // takes a string up to and including the closing quote (skipping escaped characters);
+    // returns whether the string was closed on this line
+    fn take_string(&mut self, quote: char, escapes: bool) -> bool {
+        let mut end = self.pos;
+        while end < self.chars.len() {
+            match self.chars[end] {
+                '\\' if escapes => end += 2,
+                c if c == quote => {
+                    self.take_to(end + 1, Some(STRING));
+                    return true;
+                },
+                _ => end += 1,
+            }
+        }
+        self.take_to(end, Some(STRING));
+        false
+    }
+
+    // takes text up to and including a delimiter; returns whether the delimiter was found
+    fn take_until(&mut self, delimiter: &str, color: Color) -> bool {
+        while !self.done() {
+            if self.starts_with(delimiter) {
+                self.take(delimiter.chars().count(), Some(color));
+                return true;
+            }
+            self.take(1, Some(color));
+        }
+        false
+    }
+
+    fn previous_is_word(&self) -> bool {
+        self.pos > 0 && is_word(self.chars[self.pos - 1])
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn rust_line(line: &mut Line, mut state: State) -> State {
+    loop {
+        match state {
+            State::BlockComment(mut depth) => {
+                while !line.done() && depth > 0 {
+                    if line.starts_with("*/") {
+                        depth -= 1;
+                        line.take(2, Some(COMMENT));
+                    } else if line.starts_with("/*") {
+                        depth += 1;
+                        line.take(2, Some(COMMENT));
+                    } else {
+                        line.take(1, Some(COMMENT));
+                    }
+                }
+                if depth > 0 {
+                    return State::BlockComment(depth);
+                }
+            },
+            State::Str if !line.take_string('"', true) => return State::Str,
+            State::RawStr(hashes) => {
+                let closing = format!("\"{}", "#".repeat(hashes));
+                if !line.take_until(&closing, STRING) {
+                    return State::RawStr(hashes);
+                }
+            },
+            _ => {},
+        }
+        state = State::Normal;
+
+        let c = match line.peek(0) {
+            Some(c) => c,
+            None => return State::Normal,
+        };
+        if line.starts_with("//") {
+            line.take_to(line.chars.len(), Some(COMMENT));
+        } else if line.starts_with("/*") {
+            line.take(2, Some(COMMENT));
+            state = State::BlockComment(1);
+        } else if c == '"' || (line.starts_with("b\"") && !line.previous_is_word()) {
+            line.take(if c == '"' { 1 } else { 2 }, Some(STRING));
+            state = State::Str;
+        } else if (c == 'r' || line.starts_with("br")) && !line.previous_is_word() && raw_string_start(line).is_some() {
+            let (len, hashes) = raw_string_start(line).unwrap();
+            line.take(len, Some(STRING));
+            state = State::RawStr(hashes);
+        } else if c == '\'' || (line.starts_with("b'") && !line.previous_is_word()) {
+            let start = if c == '\'' { 1 } else { 2 };
+            // a character literal, or otherwise a lifetime or loop label
+            let end = if line.peek(start) == Some('\\') {
+                line.scan_while(start + 2, |c| c != '\'') + 1
+            } else if line.peek(start + 1) == Some('\'') {
+                line.pos + start + 2
+            } else {
+                0
+            };
+            if end > line.pos {
+                line.take_to(end, Some(STRING));
+            } else {
+                let end = line.scan_while(1, is_word);
+                line.take_to(end, Some(TYPE));
+            }
+        } else if c.is_ascii_digit() && !line.previous_is_word() {
+            let mut end = line.scan_while(0, is_word);
+            while line.chars.get(end) == Some(&'.') && line.chars.get(end + 1).is_some_and(|c| c.is_ascii_digit()) {
+                end = line.scan_while(end - line.pos + 1, is_word);
+            }
+            line.take_to(end, Some(NUMBER));
+        } else if is_word(c) {
+            let end = line.scan_while(0, is_word);
+            let word: String = line.chars[line.pos..end].iter().collect();
+            if line.chars.get(end) == Some(&'!') && line.chars.get(end + 1) != Some(&'=') {
+                // a macro
+                line.take_to(end + 1, Some(NAME));
+            } else if RUST_KEYWORDS.contains(&word.as_str()) {
+                line.take_to(end, Some(KEYWORD));
+            } else if c.is_uppercase() {
+                line.take_to(end, Some(TYPE));
+            } else {
+                line.take_to(end, None);
+            }
+        } else if line.starts_with("#[") || line.starts_with("#![") {
+            // an attribute, up to its closing bracket
+            let mut depth = 0;
+            let mut end = line.pos;
+            while end < line.chars.len() {
+                match line.chars[end] {
+                    '[' => depth += 1,
+                    ']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    },
+                    _ => {},
+                }
+                end += 1;
+            }
+            line.take_to(end + 1, Some(NAME));
+        } else {
+            line.take(1, None);
+        }
+    }
+}
+
+// the length of the opening of a raw string at the current position (e.g. 'r#"') and the
+// number of '#' in it
+fn raw_string_start(line: &Line) -> Option<(usize, usize)> {
+    let prefix = if line.starts_with("br") { 2 } else { 1 };
+    let hashes = line.scan_while(prefix, |c| c == '#') - line.pos - prefix;
+    if line.peek(prefix + hashes) == Some('"') {
+        Some((prefix + hashes + 1, hashes))
+    } else {
+        None
+    }
+}
+
+fn toml_line(line: &mut Line, state: State) -> State {
+    if let State::Delimited(delimiter) = state {
+        if !line.take_until(delimiter, STRING) {
+            return state;
+        }
+    } else {
+        // a table header or the key of a key/value pair
+        let indent = line.scan_while(0, char::is_whitespace);
+        line.take_to(indent, None);
+        if line.peek(0) == Some('[') {
+            let end = line.scan_while(0, |c| c != ']');
+            let end = line.chars[end..].iter().take_while(|&&c| c == ']').count() + end;
+            line.take_to(end, Some(TYPE));
+        } else if line.peek(0).is_some_and(|c| c != '#') {
+            let mut quote = None;
+            let mut end = line.pos;
+            while end < line.chars.len() {
+                match (line.chars[end], quote) {
+                    ('=', None) => break,
+                    (c @ ('"' | '\''), None) => quote = Some(c),
+                    (c, Some(q)) if c == q => quote = None,
+                    _ => {},
+                }
+                end += 1;
+            }
+            if end < line.chars.len() {
+                let key_end = line.chars[..end].iter().rposition(|c| !c.is_whitespace()).map_or(end, |i| i + 1);
+                line.take_to(key_end, Some(NAME));
+            }
+        }
+    }
+
+    while let Some(c) = line.peek(0) {
+        if c == '#' {
+            line.take_to(line.chars.len(), Some(COMMENT));
+        } else if line.starts_with("\"\"\"") || line.starts_with("'''") {
+            let delimiter = if c == '"' { "\"\"\"" } else { "'''" };
+            line.take(3, Some(STRING));
+            if !line.take_until(delimiter, STRING) {
+                return State::Delimited(delimiter);
+            }
+        } else if c == '"' || c == '\'' {
+            line.take(1, Some(STRING));
+            line.take_string(c, c == '"');
+        } else if (c.is_ascii_digit() || ((c == '+' || c == '-') && line.peek(1).is_some_and(|c| c.is_ascii_digit())))
+            && !line.previous_is_word() {
+            // numbers, dates and times
+            let end = line.scan_while(1, |c| is_word(c) || matches!(c, '.' | ':' | '-' | '+'));
+            line.take_to(end, Some(NUMBER));
+        } else if is_word(c) {
+            let end = line.scan_while(0, is_word);
+            let word: String = line.chars[line.pos..end].iter().collect();
+            let color = if matches!(word.as_str(), "true" | "false" | "inf" | "nan") { Some(KEYWORD) } else { None };
+            line.take_to(end, color);
+        } else {
+            line.take(1, None);
+        }
+    }
+    State::Normal
+}
+
+fn json_line(line: &mut Line) -> State {
+    while let Some(c) = line.peek(0) {
+        if c == '"' {
+            let start = line.pieces.len();
+            line.take(1, Some(STRING));
+            line.take_string('"', true);
+            // a string followed by a colon is the key of an object
+            let next = line.scan_while(0, char::is_whitespace);
+            if line.chars.get(next) == Some(&':') {
+                for piece in &mut line.pieces[start..] {
+                    piece.color = Some(NAME);
+                }
+            }
+        } else if c.is_ascii_digit() || (c == '-' && line.peek(1).is_some_and(|c| c.is_ascii_digit())) {
+            let end = line.scan_while(1, |c| c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '+' | '-'));
+            line.take_to(end, Some(NUMBER));
+        } else if is_word(c) {
+            let end = line.scan_while(0, is_word);
+            let word: String = line.chars[line.pos..end].iter().collect();
+            let color = if matches!(word.as_str(), "true" | "false" | "null") { Some(KEYWORD) } else { None };
+            line.take_to(end, color);
+        } else {
+            line.take(1, None);
+        }
+    }
+    State::Normal
+}
+
+fn markdown_line(line: &mut Line, state: State) -> State {
+    let trimmed = line.rest();
+    let trimmed = trimmed.trim_start();
+
+    if let State::Fence(fence) = state {
+        let closed = trimmed.starts_with(fence.as_str());
+        line.take_to(line.chars.len(), Some(if closed { COMMENT } else { STRING }));
+        return if closed { State::Normal } else { State::Fence(fence) };
+    }
+    if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+        let fence: String = trimmed.chars().take_while(|&c| c == trimmed.chars().next().unwrap()).collect();
+        line.take_to(line.chars.len(), Some(COMMENT));
+        return State::Fence(fence);
+    }
+
+    let heading_level = trimmed.chars().take_while(|&c| c == '#').count();
+    let after_heading = trimmed.chars().nth(heading_level);
+    if (1..=6).contains(&heading_level) && after_heading.is_none_or(|c| c == ' ') {
+        line.take_to(line.chars.len(), Some(TYPE));
+        return State::Normal;
+    }
+    if trimmed.starts_with('>') || (trimmed.len() >= 3 && trimmed.chars().all(|c| c == '-' || c == '*' || c == '_')) {
+        // quotes and horizontal rules
+        line.take_to(line.chars.len(), Some(COMMENT));
+        return State::Normal;
+    }
+
+    // list items
+    let indent = line.scan_while(0, char::is_whitespace);
+    line.take_to(indent, None);
+    let digits = line.scan_while(0, |c| c.is_ascii_digit()) - line.pos;
+    let marker_len = match (line.peek(0), line.peek(1)) {
+        (Some('-' | '*' | '+'), Some(' ')) => 1,
+        _ if digits > 0 && matches!(line.peek(digits), Some('.' | ')')) && line.peek(digits + 1) == Some(' ') => digits + 1,
+        _ => 0,
+    };
+    line.take(marker_len, Some(KEYWORD));
+
+    // code spans and links within the text
+    while let Some(c) = line.peek(0) {
+        if c == '`' {
+            let ticks = line.scan_while(0, |c| c == '`') - line.pos;
+            let fence = "`".repeat(ticks);
+            line.take(ticks, Some(STRING));
+            line.take_until(&fence, STRING);
+        } else if c == '[' {
+            // a link: its text and then where it goes
+            let middle = (line.pos..line.chars.len()).find(|&i| line.chars[i] == ']' && line.chars.get(i + 1) == Some(&'('));
+            let end = middle.and_then(|middle| (middle..line.chars.len()).find(|&i| line.chars[i] == ')'));
+            match (middle, end) {
+                (Some(middle), Some(end)) => {
+                    line.take_to(middle + 1, Some(NAME));
+                    line.take_to(end + 1, Some(COMMENT));
+                },
+                _ => line.take(1, None),
+            }
+        } else {
+            line.take(1, None);
+        }
+    }
+    State::Normal
+}
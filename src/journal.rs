@@ -0,0 +1,181 @@
+// record of the changes made to the filesystem so that they can be undone and redone
+//
+// every change is described by where the affected file or directory is before and after it,
+// so undoing and redoing both come down to moving it from one place to the other; removed
+// entries are in the trash, and entries whose creation is undone are kept in a holding area
+// until the program exits
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use crate::fsops;
+use crate::names;
+use crate::platform;
+use crate::trash;
+
+// a single change to the filesystem
+pub enum Change {
+    // a file or directory was moved or renamed
+    Moved { from: PathBuf, to: PathBuf },
+    // a file or directory was created (including copies); undoing it puts it in the holding area
+    Created { path: PathBuf, held: PathBuf },
+    // a file or directory was moved into the trash; `info` is its trash information file,
+    // which is removed when the change is undone and written again when it is redone
+    Trashed { path: PathBuf, trashed: PathBuf, info: PathBuf },
+}
+
+impl Change {
+    // where the affected entry is when the change has not been made, and when it has
+    fn locations(&self) -> (&Path, &Path) {
+        match self {
+            Change::Moved { from, to } => (from, to),
+            Change::Created { path, held } => (held, path),
+            Change::Trashed { path, trashed, .. } => (path, trashed),
+        }
+    }
+
+    // makes (or with forward set to false, reverts) the change on disk; nothing is overwritten
+    // if something else has been put in the way since
+    fn apply(&self, forward: bool) -> io::Result<()> {
+        let (before, after) = self.locations();
+        let (source, target) = if forward { (before, after) } else { (after, before) };
+
+        if source.symlink_metadata().is_err() {
+            return Err(io::Error::other(format!("'{}' no longer exists", names::display_name(source.as_os_str()))));
+        }
+        if target.symlink_metadata().is_ok() {
+            return Err(io::Error::other(format!("'{}' already exists", names::display_name(target.as_os_str()))));
+        }
+
+        match self {
+            Change::Trashed { path, info, .. } if forward => {
+                trash::write_info(info, path)?;
+                fsops::move_path(source, target).inspect_err(|_| {
+                    let _ = fs::remove_file(info);
+                })
+            },
+            Change::Trashed { info, .. } => {
+                fsops::move_path(source, target)?;
+                let _ = fs::remove_file(info);
+                Ok(())
+            },
+            _ => fsops::move_path(source, target),
+        }
+    }
+}
+
+// an operation as performed by a single command
+struct Entry {
+    description: String,
+    changes: Vec<Change>,
+}
+
+// the result of undoing or redoing an operation
+pub struct Replayed {
+    pub description: String,
+    // the (from, to) moves which were made, in order
+    pub moves: Vec<(PathBuf, PathBuf)>,
+}
+
+pub struct Journal {
+    undo: Vec<Entry>,
+    redo: Vec<Entry>,
+    // directory for entries whose creation was undone; only created once it is needed
+    holding: PathBuf,
+    next_held: usize,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        // the holding area is in the data directory (or the temporary directory), which is not
+        // necessarily on the same filesystem as the user's files; undoing the creation of an
+        // entry on another filesystem copies it there (and redoing it copies it back)
+        let base = platform::data_dir().unwrap_or_else(env::temp_dir);
+        Self {
+            undo: vec![],
+            redo: vec![],
+            holding: base.join("hold").join(process::id().to_string()),
+            next_held: 0,
+        }
+    }
+
+    // records an operation which has been performed; anything which was undone can no
+    // longer be redone after this
+    pub fn record(&mut self, description: String, changes: Vec<Change>) {
+        self.undo.push(Entry { description, changes });
+        self.redo.clear();
+    }
+
+    // describes a file or directory which was just created
+    pub fn created(&mut self, path: PathBuf) -> io::Result<Change> {
+        let held = self.hold_path()?;
+        Ok(Change::Created { path, held })
+    }
+
+    // reverts the most recent operation; returns None if there is nothing to undo
+    pub fn undo(&mut self) -> io::Result<Option<Replayed>> {
+        match self.undo.pop() {
+            Some(entry) => Self::replay(entry, false, &mut self.undo, &mut self.redo).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    // performs the most recently undone operation again; returns None if there is nothing to redo
+    pub fn redo(&mut self) -> io::Result<Option<Replayed>> {
+        match self.redo.pop() {
+            Some(entry) => Self::replay(entry, true, &mut self.redo, &mut self.undo).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    // redoes (forward) or undoes an entry; on success the entry is moved to the other stack,
+    // otherwise the changes made so far are reverted and the entry is put back
+    fn replay(entry: Entry, forward: bool, from: &mut Vec<Entry>, to: &mut Vec<Entry>) -> io::Result<Replayed> {
+        // changes are undone in the reverse of the order they were made in
+        let mut order: Vec<usize> = (0..entry.changes.len()).collect();
+        if !forward {
+            order.reverse();
+        }
+
+        for (i, &index) in order.iter().enumerate() {
+            if let Err(error) = entry.changes[index].apply(forward) {
+                for &index in order[..i].iter().rev() {
+                    let _ = entry.changes[index].apply(!forward);
+                }
+                from.push(entry);
+                return Err(error);
+            }
+        }
+
+        let moves = order.iter()
+            .map(|&index| {
+                let (before, after) = entry.changes[index].locations();
+                if forward {
+                    (before.to_path_buf(), after.to_path_buf())
+                } else {
+                    (after.to_path_buf(), before.to_path_buf())
+                }
+            })
+            .collect();
+        let description = entry.description.clone();
+        to.push(entry);
+        Ok(Replayed { description, moves })
+    }
+
+    // returns an unused path in the holding area
+    fn hold_path(&mut self) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.holding)?;
+        self.next_held += 1;
+        Ok(self.holding.join(self.next_held.to_string()))
+    }
+}
+
+impl Drop for Journal {
+    // entries whose creation was undone are only recoverable while the program is running
+    fn drop(&mut self) {
+        if self.holding.exists() {
+            let _ = fs::remove_dir_all(&self.holding);
+        }
+    }
+}
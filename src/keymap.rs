@@ -0,0 +1,359 @@
+// key bindings: which action a key, a chord (a key with modifiers, e.g. 'ctrl-d') or a
+// sequence of them (e.g. 'g g') performs in each panel
+//
+// the bindings start from one of the presets ('default' or 'vim') and may be changed in the
+// [keys] section of the configuration file:
+//
+//   [keys]
+//   preset = "vim"
+//
+//   [keys.panels]          # the tree, the contents panel and the preview pane
+//   "ctrl-f" = "scroll-down"
+//   "x" = "remove"
+//
+//   [keys.contents]        # only the contents panel (also: command, tree, preview)
+//   "space" = "none"       # unbinds a key of the preset
+use std::collections::HashMap;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crate::CurrentArea;
+
+// what a key can be bound to
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    // select the command area
+    FocusCommand,
+    // select the tree from the command area
+    FocusPanels,
+    // select the panel to the left or right of the selected one
+    FocusLeft,
+    FocusRight,
+    // move the cursor of the tree or the contents panel (the preview pane scrolls by a line)
+    CursorUp,
+    CursorDown,
+    CursorFirst,
+    CursorLast,
+    ScrollUp,
+    ScrollDown,
+    ScrollLeft,
+    ScrollRight,
+    // act on the highlighted entry
+    Open,
+    Remove,
+    Rename,
+    Copy,
+    Move,
+    ToggleMark,
+}
+
+// names of the actions in the configuration file
+const ACTIONS: &[(&str, Action)] = &[
+    ("focus-command", Action::FocusCommand),
+    ("focus-panels", Action::FocusPanels),
+    ("focus-left", Action::FocusLeft),
+    ("focus-right", Action::FocusRight),
+    ("cursor-up", Action::CursorUp),
+    ("cursor-down", Action::CursorDown),
+    ("cursor-first", Action::CursorFirst),
+    ("cursor-last", Action::CursorLast),
+    ("scroll-up", Action::ScrollUp),
+    ("scroll-down", Action::ScrollDown),
+    ("scroll-left", Action::ScrollLeft),
+    ("scroll-right", Action::ScrollRight),
+    ("open", Action::Open),
+    ("remove", Action::Remove),
+    ("rename", Action::Rename),
+    ("copy", Action::Copy),
+    ("move", Action::Move),
+    ("toggle-mark", Action::ToggleMark),
+];
+
+// where a binding applies; bindings of a single panel come before those of all panels
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Scope {
+    Command,
+    // the tree, the contents panel and the preview pane
+    Panels,
+    Tree,
+    Contents,
+    Preview,
+}
+
+const SCOPES: &[(&str, Scope)] = &[
+    ("command", Scope::Command),
+    ("panels", Scope::Panels),
+    ("tree", Scope::Tree),
+    ("contents", Scope::Contents),
+    ("preview", Scope::Preview),
+];
+
+pub const PRESETS: &[&str] = &["default", "vim"];
+
+// arrows move between the panels and WASD scrolls, as before key bindings could be changed
+const DEFAULT_PRESET: &[(Scope, &str, Action)] = &[
+    (Scope::Command, "up", Action::FocusPanels),
+    (Scope::Panels, "esc", Action::FocusCommand),
+    (Scope::Panels, "left", Action::FocusLeft),
+    (Scope::Panels, "right", Action::FocusRight),
+    (Scope::Panels, "up", Action::CursorUp),
+    (Scope::Panels, "k", Action::CursorUp),
+    (Scope::Panels, "down", Action::CursorDown),
+    (Scope::Panels, "j", Action::CursorDown),
+    (Scope::Panels, "home", Action::CursorFirst),
+    (Scope::Panels, "end", Action::CursorLast),
+    (Scope::Panels, "w", Action::ScrollUp),
+    (Scope::Panels, "W", Action::ScrollUp),
+    (Scope::Panels, "a", Action::ScrollLeft),
+    (Scope::Panels, "A", Action::ScrollLeft),
+    (Scope::Panels, "s", Action::ScrollDown),
+    (Scope::Panels, "S", Action::ScrollDown),
+    (Scope::Panels, "d", Action::ScrollRight),
+    (Scope::Panels, "D", Action::ScrollRight),
+    (Scope::Panels, "enter", Action::Open),
+    (Scope::Panels, "x", Action::Remove),
+    (Scope::Panels, "delete", Action::Remove),
+    (Scope::Panels, "r", Action::Rename),
+    (Scope::Panels, "c", Action::Copy),
+    (Scope::Panels, "m", Action::Move),
+    (Scope::Contents, "space", Action::ToggleMark),
+];
+
+// hjkl moves the cursor and between the panels, and the letters which would otherwise be
+// typed into a command only act in sequences
+const VIM_PRESET: &[(Scope, &str, Action)] = &[
+    (Scope::Command, "up", Action::FocusPanels),
+    (Scope::Command, "esc", Action::FocusPanels),
+    (Scope::Panels, "esc", Action::FocusCommand),
+    (Scope::Panels, ":", Action::FocusCommand),
+    (Scope::Panels, "h", Action::FocusLeft),
+    (Scope::Panels, "left", Action::FocusLeft),
+    (Scope::Panels, "l", Action::FocusRight),
+    (Scope::Panels, "right", Action::FocusRight),
+    (Scope::Panels, "k", Action::CursorUp),
+    (Scope::Panels, "up", Action::CursorUp),
+    (Scope::Panels, "j", Action::CursorDown),
+    (Scope::Panels, "down", Action::CursorDown),
+    (Scope::Panels, "g g", Action::CursorFirst),
+    (Scope::Panels, "G", Action::CursorLast),
+    (Scope::Panels, "ctrl-u", Action::ScrollUp),
+    (Scope::Panels, "ctrl-y", Action::ScrollUp),
+    (Scope::Panels, "ctrl-d", Action::ScrollDown),
+    (Scope::Panels, "ctrl-e", Action::ScrollDown),
+    (Scope::Panels, "z h", Action::ScrollLeft),
+    (Scope::Panels, "z l", Action::ScrollRight),
+    (Scope::Panels, "enter", Action::Open),
+    (Scope::Panels, "d d", Action::Remove),
+    (Scope::Panels, "delete", Action::Remove),
+    (Scope::Panels, "c w", Action::Rename),
+    (Scope::Panels, "y y", Action::Copy),
+    (Scope::Panels, "m", Action::Move),
+    (Scope::Contents, "space", Action::ToggleMark),
+];
+
+// result of a key press
+pub enum Feed {
+    // the keys pressed so far perform an action
+    Action(Action),
+    // the keys pressed so far begin a longer binding
+    Pending,
+    // the key is not bound (and any keys pressed before it are dropped)
+    Unbound(KeyEvent),
+}
+
+pub struct Keymap {
+    bindings: HashMap<Scope, HashMap<Vec<KeyEvent>, Action>>,
+}
+
+impl Keymap {
+    pub fn preset(name: &str) -> Option<Self> {
+        let preset = match name {
+            "default" => DEFAULT_PRESET,
+            "vim" => VIM_PRESET,
+            _ => return None,
+        };
+        let mut keymap = Self { bindings: HashMap::new() };
+        for (scope, keys, action) in preset {
+            keymap.bind(*scope, parse_keys(keys).unwrap(), Some(*action));
+        }
+        Some(keymap)
+    }
+
+    // binds keys to an action, or unbinds them
+    fn bind(&mut self, scope: Scope, keys: Vec<KeyEvent>, action: Option<Action>) {
+        let bindings = self.bindings.entry(scope).or_default();
+        match action {
+            Some(action) => bindings.insert(keys, action),
+            None => bindings.remove(&keys),
+        };
+    }
+
+    // builds the key bindings from the [keys] section of the configuration file
+    pub fn from_config(keys: Option<&toml::Value>) -> Result<Self, String> {
+        let keys = match keys {
+            Some(toml::Value::Table(keys)) => keys,
+            Some(_) => return Err(String::from("[keys] must be a table")),
+            None => return Ok(Self::preset("default").unwrap()),
+        };
+
+        let mut keymap = match keys.get("preset") {
+            Some(toml::Value::String(name)) => Self::preset(name).ok_or_else(|| format!(
+                "unknown key preset '{}'; expected one of {}", name, PRESETS.join(", ")))?,
+            Some(_) => return Err(String::from("keys.preset must be a string")),
+            None => Self::preset("default").unwrap(),
+        };
+
+        for (name, bindings) in keys {
+            if name == "preset" {
+                continue;
+            }
+            let scope = SCOPES.iter().find(|(scope_name, _)| scope_name == name)
+                .map(|(_, scope)| *scope)
+                .ok_or_else(|| format!("unknown section [keys.{}]; expected one of {}",
+                    name, SCOPES.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ")))?;
+            let bindings = bindings.as_table()
+                .ok_or_else(|| format!("[keys.{}] must be a table", name))?;
+
+            for (spec, action) in bindings {
+                let keys = parse_keys(spec).map_err(|message| format!("[keys.{}]: {}", name, message))?;
+                // keys which type text cannot act in the command area
+                if scope == Scope::Command && keys.iter().any(|key| is_typed(*key)) {
+                    return Err(format!("[keys.command]: '{}' is typed into the command line and cannot be bound", spec));
+                }
+                let action = match action.as_str() {
+                    Some("none") => None,
+                    Some(action) => Some(ACTIONS.iter().find(|(name, _)| *name == action)
+                        .map(|(_, action)| *action)
+                        .ok_or_else(|| format!("[keys.{}]: unknown action '{}' for '{}'", name, action, spec))?),
+                    None => return Err(format!("[keys.{}]: the action for '{}' must be a string", name, spec)),
+                };
+                keymap.bind(scope, keys, action);
+            }
+        }
+        Ok(keymap)
+    }
+
+    // adds a key press to the keys pressed so far (which are kept until they complete or break
+    // a binding) and returns what it does in a panel
+    pub fn feed(&self, area: CurrentArea, pending: &mut Vec<KeyEvent>, key: KeyEvent) -> Feed {
+        let key = normalize(key);
+        pending.push(key);
+        match self.lookup(area, pending) {
+            Lookup::Action(action) => {
+                pending.clear();
+                Feed::Action(action)
+            },
+            Lookup::Pending => Feed::Pending,
+            // a key which breaks a sequence may still begin a binding of its own
+            Lookup::None if pending.len() > 1 => {
+                pending.clear();
+                self.feed(area, pending, key)
+            },
+            Lookup::None => {
+                pending.clear();
+                Feed::Unbound(key)
+            },
+        }
+    }
+
+    fn lookup(&self, area: CurrentArea, keys: &[KeyEvent]) -> Lookup {
+        let scopes: &[Scope] = match area {
+            CurrentArea::Command => &[Scope::Command],
+            CurrentArea::Tree => &[Scope::Tree, Scope::Panels],
+            CurrentArea::Contents => &[Scope::Contents, Scope::Panels],
+            CurrentArea::Preview => &[Scope::Preview, Scope::Panels],
+        };
+        let mut pending = false;
+        for scope in scopes {
+            let bindings = match self.bindings.get(scope) {
+                Some(bindings) => bindings,
+                None => continue,
+            };
+            if let Some(action) = bindings.get(keys) {
+                return Lookup::Action(*action);
+            }
+            pending |= bindings.keys().any(|bound| bound.len() > keys.len() && bound.starts_with(keys));
+        }
+        if pending { Lookup::Pending } else { Lookup::None }
+    }
+}
+
+enum Lookup {
+    Action(Action),
+    Pending,
+    None,
+}
+
+// the shift of a typed character is part of the character itself ('G' rather than 'shift-g')
+fn normalize(key: KeyEvent) -> KeyEvent {
+    match key.code {
+        KeyCode::Char(_) => KeyEvent::new(key.code, key.modifiers - KeyModifiers::SHIFT),
+        _ => key,
+    }
+}
+
+// whether a key types a character into the command line
+fn is_typed(key: KeyEvent) -> bool {
+    matches!(key.code, KeyCode::Char(_)) && (key.modifiers - KeyModifiers::SHIFT).is_empty()
+}
+
+// parses a sequence of keys separated by spaces, e.g. 'g g' or 'ctrl-x ctrl-s'
+fn parse_keys(spec: &str) -> Result<Vec<KeyEvent>, String> {
+    let keys = spec.split_whitespace().map(parse_key).collect::<Result<Vec<_>, _>>()?;
+    if keys.is_empty() {
+        return Err(String::from("a key binding must name at least one key"));
+    }
+    Ok(keys)
+}
+
+// parses a key with optional modifiers, e.g. 'x', 'G', 'enter', 'ctrl-d' or 'alt-left'
+fn parse_key(spec: &str) -> Result<KeyEvent, String> {
+    let mut modifiers = KeyModifiers::empty();
+    let mut name = spec;
+    loop {
+        let (modifier, rest) = match name.split_once('-') {
+            // a lone '-' is the key itself
+            Some((modifier, rest)) if !rest.is_empty() => (modifier, rest),
+            _ => break,
+        };
+        modifiers |= match modifier {
+            "ctrl" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return Err(format!("unknown modifier '{}' in '{}'", modifier, spec)),
+        };
+        name = rest;
+    }
+
+    let code = match name {
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "backspace" => KeyCode::Backspace,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "delete" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "space" => KeyCode::Char(' '),
+        _ if name.starts_with('f') && name[1..].parse::<u8>().is_ok_and(|n| (1..=12).contains(&n)) =>
+            KeyCode::F(name[1..].parse().unwrap()),
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => return Err(format!("unknown key '{}' in '{}'", name, spec)),
+            }
+        },
+    };
+    Ok(normalize(match code {
+        KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+            KeyEvent::new(KeyCode::Char(c.to_ascii_uppercase()), modifiers)
+        },
+        _ => KeyEvent::new(code, modifiers),
+    }))
+}
@@ -0,0 +1,332 @@
+// the order of the entries listed in the contents panel, and which of them are listed; the
+// tree itself is always kept sorted by name, and the listing is sorted when it is shown
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use crate::pattern::{self, NameMatcher};
+use crate::platform::PlatformMetadata;
+use crate::{ContentsView, Directory, DirectoryRef, File, FileRef, StateManager, Vector2};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Name,
+    Size,
+    Modified,
+    Extension,
+}
+
+impl SortKey {
+    pub const NAMES: &'static [&'static str] = &["name", "size", "mtime", "ext"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "name" => Some(SortKey::Name),
+            "size" => Some(SortKey::Size),
+            "mtime" => Some(SortKey::Modified),
+            "ext" => Some(SortKey::Extension),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Sort {
+    pub key: SortKey,
+    pub descending: bool,
+    // whether numbers in names are ordered by their value ('file2' before 'file10')
+    pub natural: bool,
+    pub ignore_case: bool,
+}
+
+impl Sort {
+    // orders two entries by the sort key, then by name; directories have no size, so they are
+    // ordered by name when sorting by size
+    fn compare(&self, x: &Entry, y: &Entry) -> Ordering {
+        let by_key = match self.key {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Size => x.size.cmp(&y.size),
+            SortKey::Modified => x.modified.cmp(&y.modified),
+            SortKey::Extension => match (extension(x.name), extension(y.name)) {
+                (Some(x), Some(y)) => self.compare_names(x, y),
+                (x, y) => x.cmp(&y),
+            },
+        };
+        let ordering = by_key.then_with(|| self.compare_names(x.name, y.name));
+        if self.descending { ordering.reverse() } else { ordering }
+    }
+
+    // names which are the same apart from case (or leading zeros) are ordered as they are
+    // without the options, so that the order does not depend on the order in the tree
+    fn compare_names(&self, x: &OsStr, y: &OsStr) -> Ordering {
+        if !self.natural && !self.ignore_case {
+            return x.cmp(y);
+        }
+        fn fold(name: &OsStr, ignore_case: bool) -> Cow<'_, str> {
+            match name.to_string_lossy() {
+                name if ignore_case => Cow::Owned(name.to_lowercase()),
+                name => name,
+            }
+        }
+        let (folded_x, folded_y) = (fold(x, self.ignore_case), fold(y, self.ignore_case));
+        let ordering = if self.natural {
+            natural_cmp(&folded_x, &folded_y)
+        } else {
+            folded_x.cmp(&folded_y)
+        };
+        ordering.then_with(|| x.cmp(y))
+    }
+
+    // e.g. 'size desc, natural'
+    pub fn describe(&self) -> String {
+        let mut description = format!("{} {}", SortKey::NAMES[self.key as usize], if self.descending { "desc" } else { "asc" });
+        if self.natural {
+            description.push_str(", natural");
+        }
+        if self.ignore_case {
+            description.push_str(", ignore case");
+        }
+        description
+    }
+}
+
+// compares text with runs of digits compared by their value
+fn natural_cmp(mut x: &str, mut y: &str) -> Ordering {
+    loop {
+        match (x.chars().next(), y.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a), Some(b)) if a.is_ascii_digit() && b.is_ascii_digit() => {
+                let x_end = x.find(|c: char| !c.is_ascii_digit()).unwrap_or(x.len());
+                let y_end = y.find(|c: char| !c.is_ascii_digit()).unwrap_or(y.len());
+                let (x_number, y_number) = (x[..x_end].trim_start_matches('0'), y[..y_end].trim_start_matches('0'));
+                let ordering = x_number.len().cmp(&y_number.len()).then_with(|| x_number.cmp(y_number));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                x = &x[x_end..];
+                y = &y[y_end..];
+            },
+            (Some(a), Some(b)) => {
+                if a != b {
+                    return a.cmp(&b);
+                }
+                x = &x[a.len_utf8()..];
+                y = &y[b.len_utf8()..];
+            },
+        }
+    }
+}
+
+// limits the contents panel to the entries whose names match a pattern
+pub struct Filter {
+    // the pattern as it was given
+    pub text: String,
+    matcher: NameMatcher,
+}
+
+// what an entry is sorted by
+struct Entry<'e> {
+    name: &'e OsStr,
+    size: Option<u64>,
+    modified: Option<SystemTime>,
+}
+
+impl<'e> Entry<'e> {
+    fn of_file(file: &'e File) -> Self {
+        Self {
+            name: &file.name,
+            size: file.meta.as_ref().map(|meta| meta.file_size()),
+            modified: file.meta.as_ref().and_then(|meta| meta.timestamps().modified),
+        }
+    }
+
+    fn of_dir(dir: &'e Directory) -> Self {
+        Self {
+            name: &dir.name,
+            size: None,
+            modified: dir.meta.as_ref().and_then(|meta| meta.timestamps().modified),
+        }
+    }
+}
+
+fn extension(name: &OsStr) -> Option<&OsStr> {
+    Path::new(name).extension()
+}
+
+impl<'a> StateManager<'a> {
+    // the files and directories of the current directory in the order they are listed in the
+    // contents panel
+    pub fn listed_entries(&self) -> (Vec<FileRef>, Vec<DirectoryRef>) {
+        let sort = self.sort;
+        let dir = self.curr_dir.borrow();
+        let shown = |name: &OsStr| self.filter.as_ref().is_none_or(|filter| filter.matcher.is_match(name));
+
+        let mut files: Vec<_> = dir.files.iter().filter(|file| shown(&file.borrow().name)).cloned().collect();
+        files.sort_by(|x, y| sort.compare(&Entry::of_file(&x.borrow()), &Entry::of_file(&y.borrow())));
+
+        let mut dirs: Vec<_> = dir.directories.iter().filter(|dir| shown(&dir.borrow().name)).cloned().collect();
+        dirs.sort_by(|x, y| sort.compare(&Entry::of_dir(&x.borrow()), &Entry::of_dir(&y.borrow())));
+
+        (files, dirs)
+    }
+
+    // shown at the end of the heading of the contents panel, e.g. '[sort: size desc] [filter: *.rs]'
+    pub fn listing_indicator(&self) -> String {
+        let mut indicator = format!("[sort: {}]", self.sort.describe());
+        if let Some(filter) = &self.filter {
+            indicator.push_str(&format!(" [filter: {}]", filter.text));
+        }
+        indicator
+    }
+
+    // sort <name|size|mtime|ext> [asc|desc] [--natural] [--ignore-case]
+    pub fn sort(&mut self, args: &[OsString]) -> io::Result<()> {
+        let key = match args[0].to_str().and_then(SortKey::from_name) {
+            Some(key) => key,
+            None => return self.print_error(&format!("Unknown sort '{}'; use {}", args[0].to_string_lossy(), SortKey::NAMES.join(", "))),
+        };
+        let mut sort = Sort { key, descending: false, natural: false, ignore_case: false };
+        for option in &args[1..] {
+            match option.to_str() {
+                Some("asc") => sort.descending = false,
+                Some("desc") => sort.descending = true,
+                Some("--natural") => sort.natural = true,
+                Some("--ignore-case") => sort.ignore_case = true,
+                _ => return self.print_error(&format!("Unknown option '{}'; use asc, desc, --natural or --ignore-case",
+                    option.to_string_lossy())),
+            }
+        }
+        self.change_listing(|manager| manager.sort = sort)
+    }
+
+    // filter <pattern>
+    // filter
+    pub fn filter(&mut self, arg: Option<&OsStr>) -> io::Result<()> {
+        let filter = match arg {
+            Some(arg) => match pattern::parse_name(arg) {
+                Ok(matcher) => Some(Filter { text: arg.to_string_lossy().into_owned(), matcher }),
+                Err(message) => return self.print_error(&message),
+            },
+            None => None,
+        };
+        self.change_listing(|manager| manager.filter = filter)
+    }
+
+    // shows the listing of the current directory after changing its order or filter; the
+    // highlighted entry stays highlighted if it is still listed
+    fn change_listing(&mut self, change: impl FnOnce(&mut Self)) -> io::Result<()> {
+        let highlighted: Option<PathBuf> = match self.contents_view {
+            ContentsView::Directory => self.contents_cursor
+                .and_then(|cursor| self.current_entries().into_iter().nth(cursor))
+                .map(|(_, path)| path),
+            _ => None,
+        };
+        change(self);
+
+        self.contents_view = ContentsView::Directory;
+        self.contents_cursor = None;
+        self.dir_contents.curr_pos = Vector2 { x: 0, y: 0 };
+        match highlighted.and_then(|path| self.current_entries().iter().position(|(_, entry)| *entry == path)) {
+            Some(cursor) => self.set_contents_cursor(cursor),
+            None => self.refresh_area(false, true),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sort(natural: bool, ignore_case: bool) -> Sort {
+        Sort { key: SortKey::Name, descending: false, natural, ignore_case }
+    }
+
+    fn sorted<'n>(sort: Sort, names: &[&'n str]) -> Vec<&'n str> {
+        let mut names = names.to_vec();
+        names.sort_by(|x, y| sort.compare_names(OsStr::new(x), OsStr::new(y)));
+        names
+    }
+
+    #[test]
+    fn plain_order() {
+        assert_eq!(sorted(sort(false, false), &["b", "file10", "B", "file2", "a"]), ["B", "a", "b", "file10", "file2"]);
+    }
+
+    #[test]
+    fn numbers_by_value() {
+        assert_eq!(sorted(sort(true, false), &["file10", "file2", "file1", "file"]), ["file", "file1", "file2", "file10"]);
+        assert_eq!(natural_cmp("18446744073709551616", "18446744073709551615"), Ordering::Greater);
+        assert_eq!(natural_cmp("9", "10"), Ordering::Less);
+    }
+
+    #[test]
+    fn leading_zeros() {
+        assert_eq!(natural_cmp("a01", "a1"), Ordering::Equal);
+        assert_eq!(natural_cmp("a002", "a1"), Ordering::Greater);
+        assert_eq!(natural_cmp("a0", "a"), Ordering::Greater);
+        assert_eq!(natural_cmp("a00", "a0"), Ordering::Equal);
+        // names which are only equal by value keep a fixed order
+        assert_eq!(sorted(sort(true, false), &["a1", "a001", "a01", "a02"]), ["a001", "a01", "a1", "a02"]);
+        assert_eq!(sorted(sort(true, false), &["a01", "a1", "a001"]), ["a001", "a01", "a1"]);
+    }
+
+    #[test]
+    fn mixed_runs() {
+        assert_eq!(sorted(sort(true, false), &["v1.10.0", "v1.9.2", "v1.9.10", "v1.9"]), ["v1.9", "v1.9.2", "v1.9.10", "v1.10.0"]);
+        assert_eq!(sorted(sort(true, false), &["x10y2", "x9y10", "x10y1", "x9"]), ["x9", "x9y10", "x10y1", "x10y2"]);
+        // digits come before letters, as they do without the option
+        assert_eq!(sorted(sort(true, false), &["a", "1", "a1", "1a"]), ["1", "1a", "a", "a1"]);
+    }
+
+    #[test]
+    fn ignoring_case() {
+        assert_eq!(sorted(sort(false, true), &["b", "B", "a", "A", "C"]), ["A", "a", "B", "b", "C"]);
+        assert_eq!(sorted(sort(true, true), &["File10", "file9", "FILE9"]), ["FILE9", "file9", "File10"]);
+    }
+
+    #[test]
+    fn non_ascii() {
+        assert_eq!(sorted(sort(false, true), &["éclair", "Zebra", "Éclair", "apple"]), ["apple", "Zebra", "Éclair", "éclair"]);
+        assert_eq!(sorted(sort(true, true), &["Ärger10", "ärger9", "ÄRGER9"]), ["ÄRGER9", "ärger9", "Ärger10"]);
+        // only ASCII digits are numbers
+        assert_eq!(sorted(sort(true, false), &["٣", "10", "2"]), ["2", "10", "٣"]);
+    }
+
+    #[test]
+    fn deterministic_tie_break() {
+        // whatever order the names start in, they end up in the same order
+        let names = ["b", "B", "b01", "B1", "b1", "B01"];
+        for natural in [false, true] {
+            for ignore_case in [false, true] {
+                let sort = sort(natural, ignore_case);
+                let expected = sorted(sort, &names);
+                let mut reversed = names;
+                reversed.reverse();
+                assert_eq!(sorted(sort, &reversed), expected);
+                for pair in expected.windows(2) {
+                    assert_eq!(sort.compare_names(OsStr::new(pair[0]), OsStr::new(pair[1])), Ordering::Less);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn keys_then_names() {
+        let entry = |name, size| Entry { name: OsStr::new(name), size, modified: None };
+        let (a, b, c) = (entry("a.txt", Some(5)), entry("b.rs", Some(5)), entry("c.txt", Some(1)));
+        let mut sort = Sort { key: SortKey::Size, descending: false, natural: false, ignore_case: false };
+        assert_eq!(sort.compare(&c, &a), Ordering::Less);
+        assert_eq!(sort.compare(&a, &b), Ordering::Less);
+        sort.descending = true;
+        assert_eq!(sort.compare(&a, &b), Ordering::Greater);
+        sort = Sort { key: SortKey::Extension, descending: false, natural: false, ignore_case: false };
+        assert_eq!(sort.compare(&b, &a), Ordering::Less);
+        assert_eq!(sort.compare(&a, &c), Ordering::Less);
+        // entries without an extension come first
+        assert_eq!(sort.compare(&entry("Makefile", None), &b), Ordering::Less);
+    }
+}
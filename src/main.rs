@@ -1,20 +1,57 @@
+mod args;
+mod columns;
+mod command;
+mod config;
+mod external;
+mod file_colors;
+mod find;
+mod fsops;
+mod grep;
+mod highlight;
+mod journal;
+mod keymap;
+mod listing;
+mod marks;
+mod names;
+mod navigation;
+mod pattern;
+mod platform;
+mod preview;
+mod rename;
+mod scan;
+mod shell_init;
+mod theme;
+mod trash;
+
 use std::env;
 use std::fs::{self, Metadata};
-use std::os::windows::prelude::*;
-use std::ffi::OsString;
-use std::path::PathBuf;
+use std::ffi::{OsStr, OsString};
+use std::path::{Component, Path, PathBuf};
+use std::process;
 use std::io;
 use std::ops::{Add, AddAssign, Sub};
 use std::cmp::{PartialEq, max, min};
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
+use std::collections::{BTreeSet, HashSet};
 use console::Term;
 use crossterm::event::{self, Event};
-use chrono::{DateTime, Utc, Datelike, Timelike};
-use colorful::Color;
-use colorful::Colorful;
-
-#[derive(Debug, Clone, Copy)]
+use crossterm::terminal;
+use theme::{paint, Color};
+use config::Config;
+use find::{FindResults, RunningSearch};
+use highlight::Highlighter;
+use journal::Journal;
+use keymap::{Action, Feed};
+use navigation::CursorMove;
+use preview::Preview;
+use rename::RenamePlan;
+use listing::{Filter, Sort};
+use scan::{ScannedEntry, Scanner};
+use trash::Trash;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 struct Vector2 {
     x: usize,
     y: usize,
@@ -54,16 +91,35 @@ impl Sub for Vector2 {
 // struct for data relevant to a file in the directory
 struct File {
     name: OsString,
-    meta: Metadata,
+    // None if the metadata could not be read
+    meta: Option<Metadata>,
     full_path: PathBuf,
+    // reason the file could not be read (e.g. a broken symbolic link)
+    error: Option<String>,
+    // whether this is a symbolic link (meta is then that of its target)
+    is_link: bool,
 }
 
 impl File {
     fn new(path: PathBuf) -> Self {
+        let (meta, error) = scan::read_metadata(&path);
+        let is_link = path.symlink_metadata().is_ok_and(|meta| meta.file_type().is_symlink());
         Self {
             name: OsString::from(path.file_name().unwrap()),
-            meta: path.metadata().unwrap(),
+            meta,
             full_path: path,
+            error,
+            is_link,
+        }
+    }
+
+    fn from_entry(entry: ScannedEntry) -> Self {
+        Self {
+            name: OsString::from(entry.path.file_name().unwrap()),
+            meta: entry.meta,
+            full_path: entry.path,
+            error: entry.error,
+            is_link: entry.is_link,
         }
     }
 }
@@ -71,29 +127,124 @@ impl File {
 // struct for data relevant to a directory
 struct Directory {
     name: OsString,
-    meta: Metadata,
+    // None if the metadata could not be read
+    meta: Option<Metadata>,
     full_path: PathBuf,
+    // reason the directory (or its contents) could not be read
+    error: Option<String>,
     files: Vec<FileRef>,
     directories: Vec<DirectoryRef>,
     parent: Option<DirectoryRef>,
+    // whether the contents of this directory have been read yet; directories are read
+    // lazily so that huge trees do not need to be read up front
+    loaded: bool,
 }
 
 impl Directory {
-    fn new(
-        path: PathBuf,
-        files: Vec<FileRef>,
-        directories: Vec<DirectoryRef>,
-        parent: Option<DirectoryRef>
-    ) -> Self
-    {
+    fn new(path: PathBuf, parent: Option<DirectoryRef>) -> Self {
+        let (meta, error) = scan::read_metadata(&path);
         Self {
             name: OsString::from(path.file_name().unwrap()),
-            meta: path.metadata().unwrap(),
+            meta,
             full_path: path,
-            files,
-            directories,
+            error,
+            files: vec![],
+            directories: vec![],
             parent,
+            loaded: false,
+        }
+    }
+
+    fn from_entry(entry: ScannedEntry, parent: Option<DirectoryRef>) -> Self {
+        Self {
+            name: OsString::from(entry.path.file_name().unwrap()),
+            meta: entry.meta,
+            full_path: entry.path,
+            error: entry.error,
+            files: vec![],
+            directories: vec![],
+            parent,
+            loaded: false,
+        }
+    }
+
+    // records that the contents of a directory could not be read; it is still considered
+    // loaded so that reading it is not attempted over and over
+    fn set_unreadable(this: &DirectoryRef, error: &io::Error) {
+        let mut this = this.borrow_mut();
+        this.error = Some(scan::describe_error(error));
+        this.files.clear();
+        this.directories.clear();
+        this.loaded = true;
+    }
+
+    // returns the reason of the first entry in this directory's (loaded) subtree
+    // which could not be read, if any
+    fn find_error(this: &DirectoryRef) -> Option<String> {
+        let dir = this.borrow();
+        dir.error.clone()
+            .or_else(|| dir.files.iter().find_map(|f| f.borrow().error.clone()))
+            .or_else(|| dir.directories.iter().find_map(Directory::find_error))
+    }
+
+    // number of levels below the root directory
+    fn depth(&self) -> usize {
+        let mut depth = 0;
+        let mut parent = self.parent.clone();
+        while let Some(dir) = parent {
+            depth += 1;
+            parent = dir.borrow().parent.clone();
+        }
+        depth
+    }
+
+    // changes the location of a directory, updating the paths of everything inside of it
+    fn set_path(this: &DirectoryRef, path: PathBuf) {
+        let mut dir = this.borrow_mut();
+        dir.name = OsString::from(path.file_name().unwrap());
+        for file in &dir.files {
+            let mut file = file.borrow_mut();
+            file.full_path = path.join(&file.name);
+        }
+        for child in &dir.directories {
+            let child_path = path.join(&child.borrow().name);
+            Directory::set_path(child, child_path);
+        }
+        dir.full_path = path;
+    }
+
+    // replaces the contents of this directory with freshly read entries; child directories
+    // which were already in the tree are kept (along with their own contents)
+    fn apply_entries(this: &DirectoryRef, entries: Vec<ScannedEntry>) {
+        let mut files = vec![];
+        let mut directories = vec![];
+        for entry in entries {
+            if entry.is_dir {
+                let name = entry.path.file_name().unwrap();
+                let existing = this.borrow().directories.iter().find(|d| d.borrow().name == name).cloned();
+                match existing {
+                    Some(dir) => {
+                        dir.borrow_mut().meta = entry.meta;
+                        dir.borrow_mut().error = entry.error;
+                        directories.push(dir);
+                    },
+                    None => directories.push(Rc::new(RefCell::new(
+                        Directory::from_entry(entry, Some(this.clone()))
+                    ))),
+                }
+            } else {
+                files.push(Rc::new(RefCell::new(File::from_entry(entry))));
+            }
         }
+
+        // files/dirs are kept sorted
+        files.sort_by(|x: &FileRef, y| x.borrow().name.cmp(&y.borrow().name));
+        directories.sort_by(|x: &DirectoryRef, y| x.borrow().name.cmp(&y.borrow().name));
+
+        let mut this = this.borrow_mut();
+        this.files = files;
+        this.directories = directories;
+        this.loaded = true;
     }
 }
 
@@ -102,10 +253,11 @@ type FileRef = Rc<RefCell<File>>;
 
 impl PartialEq for Directory {
     fn eq(&self, other: &Self) -> bool {
-        self as *const Self == other as *const Self
+        std::ptr::eq(self, other)
     }
 }
 
+#[derive(Clone)]
 struct ColoredString {
     string: String,
     color: Option<Color>,
@@ -131,10 +283,40 @@ struct ScrollableArea {
 }
 
 impl ScrollableArea {
+    // an empty area; it is given its place on the screen by StateManager::layout
+    fn new() -> Self {
+        Self {
+            screen_offset: Vector2 { x: 0, y: 0 },
+            size: Vector2 { x: 0, y: 0 },
+            curr_pos: Vector2 { x: 0, y: 0 },
+            contents: vec![],
+            longest_line_len: 0,
+        }
+    }
+
     fn contents_size(&self) -> Vector2 {
         self.size - Vector2 { x: 4, y: 2 }
     }
 
+    // scrolls by up to the given number of rows and columns (negative for up and left) without
+    // going past the contents, and draws the area again if it moved
+    fn scroll(&mut self, term: &Term, rows: isize, columns: isize) -> io::Result<()> {
+        let scrolled = |pos: usize, by: isize, end: usize| if by < 0 {
+            pos.saturating_sub(by.unsigned_abs())
+        } else {
+            min(pos.saturating_add(by as usize), max(pos, end))
+        };
+        let pos = Vector2 {
+            x: scrolled(self.curr_pos.x, columns, self.longest_line_len.saturating_sub(self.contents_size().x)),
+            y: scrolled(self.curr_pos.y, rows, self.contents.len().saturating_sub(self.contents_size().y)),
+        };
+        if pos != self.curr_pos {
+            self.curr_pos = pos;
+            self.draw(term)?;
+        }
+        Ok(())
+    }
+
     fn draw(&self, term: &Term) -> io::Result<()> {
         #[derive(Clone, Copy)]
         enum ArrowLocation {
@@ -176,7 +358,7 @@ impl ScrollableArea {
             let mut pos = self.screen_offset + begin_offset;
             for _ in 0..count {
                 term.move_cursor_to(pos.x, pos.y)?;
-                term.write_str(&format!("{}", arrow.color(Color::DarkGray)))?;
+                term.write_str(&paint(arrow, Color::Muted))?;
 
                 if horizontal {
                     pos.x += X_EVERY;
@@ -221,10 +403,7 @@ impl ScrollableArea {
                     }
 
                     // how many characters in the line to skip based on current screen position
-                    let skip = match self.curr_pos.x.checked_sub(piece_begin) {
-                        Some(i) => i,
-                        None => 0,
-                    };
+                    let skip = self.curr_pos.x.saturating_sub(piece_begin);
                     // how many characters in total are needed
                     let take = contents_size.x - ((piece_begin + skip) - self.curr_pos.x);
 
@@ -234,7 +413,7 @@ impl ScrollableArea {
 
                     // print in color if needed
                     term.write_str(&if let Some(color) = &piece.color {
-                        format!("{}", substr.color(*color))
+                        paint(&substr, *color)
                     } else {
                         substr
                     })?;
@@ -252,16 +431,51 @@ impl ScrollableArea {
 }
 
 #[derive(Clone, Copy, PartialEq)]
-enum CurrentArea { Command, Tree, Contents }
+enum CurrentArea { Command, Tree, Contents, Preview }
+
+// what is being shown in the contents panel
+#[derive(Clone, Copy, PartialEq)]
+enum ContentsView {
+    // the files and directories in the current directory
+    Directory,
+    // every entry in the tree which could not be read
+    Errors,
+    // the entries in the trash
+    Trash,
+    // the entries matched by a pattern argument, before a command is applied to them
+    Selection,
+    // the old and new names of the entries to be renamed by 'rename-batch'
+    RenamePreview,
+    // the results of the last search
+    FindResults,
+    // the file shown by 'view', when the preview pane is not open
+    Preview,
+}
+
+// an action which is waiting for the user to answer yes/no
+enum Confirmation {
+    EmptyTrash,
+    // permanently removing the marked entries
+    RemoveMarked,
+    // moving the entries matched by a pattern into a directory
+    MoveSelection(DirQuery),
+    // removing the entries matched by a pattern
+    RemoveSelection { permanent: bool },
+    // making the renames of the previewed plan
+    RenameBatch,
+}
 
 // used with command buffering for finding the appropriate directory in the directory tree as queried
 enum DirQuery {
     Disambiguated(DirectoryRef),
-    ByName(String),
+    ByName(OsString),
 }
 
+// argument of the remove command for removing permanently instead of moving to the trash
+const PERMANENT_FLAG: &str = "--permanent";
+
 // a function that will be used for command buffering if a directory ambiguity is present
-type CommandProcedure<'a> = fn(&mut StateManager<'a>, DirQuery, &str) -> io::Result<()>;
+type CommandProcedure<'a> = fn(&mut StateManager<'a>, DirQuery, &OsStr) -> io::Result<()>;
 
 // object which manages 'global' state of the program
 struct StateManager<'a> {
@@ -276,39 +490,71 @@ struct StateManager<'a> {
     // all possible directories which could match ambiguous query
     ambiguous_dirs: Vec<DirectoryRef>,
     // maybe buffered command (to remember which command was called in the case of ambiguity)
-    command_buf: Option<(CommandProcedure<'a>, String)>,
+    command_buf: Option<(CommandProcedure<'a>, OsString)>,
     // flag for whether an error message was printed in prior command
     error_message_active: bool,
     // buffer for a directory to be removed as user is asked to confirm if directory removal was intended
     dir_to_remove: Option<DirectoryRef>,
+    // action which the user is being asked to confirm, other than removing a directory
+    confirmation: Option<Confirmation>,
+    // paths of the marked entries, which batch commands apply to
+    marks: BTreeSet<PathBuf>,
+    // index of the highlighted entry in the contents panel (files first, then directories)
+    contents_cursor: Option<usize>,
+    // directory highlighted in the tree, once the cursor has been moved there
+    tree_cursor: Option<DirectoryRef>,
+    // paths of the entries matched by a pattern argument, which the current batch command
+    // applies to instead of the marked entries
+    selection: Option<Vec<PathBuf>>,
+    // renames worked out by 'rename-batch', shown until they are confirmed or dropped
+    rename_plan: Option<RenamePlan>,
+    // results of the last 'find', which other commands can refer to as '#N'
+    find_results: Option<FindResults>,
+    // 'find' or 'grep' running in the background, whose results replace find_results once it finishes
+    search: Option<RunningSearch>,
+    // file shown by 'view', in the contents panel or the preview pane
+    preview: Option<Preview>,
+    // drawing area for the preview pane on the right, when it is open
+    preview_area: Option<ScrollableArea>,
+    // background highlighter for previews of source files
+    highlighter: Highlighter,
     // drawing area for directory tree
     tree: ScrollableArea,
     // drawing area for contents of currently selected directory
     dir_contents: ScrollableArea,
+    // what the contents area is currently showing
+    contents_view: ContentsView,
+    // background reader for directories which have not been loaded yet
+    scanner: Scanner,
+    // directories which have been queued in the scanner but not yet read
+    pending_scans: HashSet<PathBuf>,
+    // how many levels deep the background scan goes (None for no limit)
+    max_depth: Option<usize>,
+    // whether hidden files and directories are loaded into the tree
+    show_hidden: bool,
+    // whether --show-hidden was given, which shows them whatever the configuration says
+    show_hidden_flag: bool,
+    // whether colors were turned off with --no-color
+    no_color_flag: bool,
+    // whether the background scan has added to the tree since it was last drawn
+    scan_redraw_needed: bool,
+    last_scan_redraw: Instant,
+    // changes made by commands, for undo/redo
+    journal: Journal,
+    // where removed entries go; None if the trash's location cannot be determined
+    trash: Option<Trash>,
+    // order of the contents panel, initially that of the configuration; changed with 'sort'
+    sort: Sort,
+    // entries shown in the contents panel; changed with 'filter'
+    filter: Option<Filter>,
+    // settings from the configuration file
+    config: Config,
 }
 
 impl<'a> StateManager<'a> {
     // returns a new instance of the StateManager with all needed values initialized
-    fn init(term: &'a Term, root: DirectoryRef) -> io::Result<Self> {
-        let term_size = Vector2 { x: term.size().1 as usize, y: term.size().0 as usize };
-        let line_x = (term_size.x as f64 * 0.5) as usize;
-
-        let tree_area = ScrollableArea {
-            screen_offset: Vector2 { x: 0, y: 2 },
-            size: Vector2 { x: line_x, y: term_size.y - 4 },
-            curr_pos: Vector2 { x: 0, y: 0 },
-            longest_line_len: 0,
-            contents: vec![],
-        };
-
-        let contents_area = ScrollableArea {
-            screen_offset: Vector2 { x: line_x + 1, y: 2 },
-            size: Vector2 { x: (term_size.x - line_x - 1), y: term_size.y - 4 },
-            curr_pos: Vector2 { x: 0, y: 0 },
-            longest_line_len: 0,
-            contents: vec![],
-        };
-
+    fn init(term: &'a Term, root: DirectoryRef, max_depth: Option<usize>, show_hidden_flag: bool, no_color_flag: bool, config: Config) -> io::Result<Self> {
+        let show_hidden = show_hidden_flag || config.show_hidden;
         let mut new = Self {
             term,
             root: root.clone(),
@@ -318,19 +564,68 @@ impl<'a> StateManager<'a> {
             command_buf: None,
             error_message_active: false,
             dir_to_remove: None,
-            tree: tree_area,
-            dir_contents: contents_area,
+            confirmation: None,
+            marks: BTreeSet::new(),
+            contents_cursor: None,
+            tree_cursor: None,
+            selection: None,
+            rename_plan: None,
+            find_results: None,
+            search: None,
+            preview: None,
+            preview_area: None,
+            highlighter: Highlighter::start(),
+            tree: ScrollableArea::new(),
+            dir_contents: ScrollableArea::new(),
+            contents_view: ContentsView::Directory,
+            scanner: Scanner::start(show_hidden),
+            pending_scans: HashSet::new(),
+            max_depth,
+            show_hidden,
+            show_hidden_flag,
+            no_color_flag,
+            scan_redraw_needed: false,
+            last_scan_redraw: Instant::now(),
+            journal: Journal::new(),
+            trash: Trash::open(),
+            sort: config.sort,
+            filter: None,
+            config,
         };
 
+        new.layout();
+        new.ensure_loaded(&root)?;
         new.refresh_area(true, true)?;
 
         Ok(new)
     }
 
+    // places the panels on the screen according to the size of the terminal
+    fn layout(&mut self) {
+        let width = self.term.size().1 as usize;
+        let height = self.term.size().0 as usize;
+        let (line_x, preview_line_x) = self.config.divider_positions(width, self.preview_area.is_some());
+
+        self.tree.screen_offset = Vector2 { x: 0, y: 2 };
+        self.tree.size = Vector2 { x: line_x, y: height - 4 };
+        self.dir_contents.screen_offset = Vector2 { x: line_x + 1, y: 2 };
+        self.dir_contents.size = Vector2 { x: preview_line_x.unwrap_or(width) - line_x - 1, y: height - 4 };
+        if let (Some(area), Some(preview_line_x)) = (&mut self.preview_area, preview_line_x) {
+            area.screen_offset = Vector2 { x: preview_line_x + 1, y: 2 };
+            area.size = Vector2 { x: width - preview_line_x - 1, y: height - 4 };
+        }
+    }
+
     // processes a user command and updates the directory contents if needed
     fn process_command(&mut self, command: &str) -> io::Result<()> {
-        let tokens: Vec<&str> = command.split_whitespace().collect();
-        if tokens.len() == 0 {
+        let tokens = match command::tokenize(command) {
+            Ok(tokens) => tokens,
+            Err(message) => {
+                self.print_error(&message)?;
+                return Ok(());
+            },
+        };
+        if tokens.is_empty() {
             self.print_error("Enter a command")?;
             return Ok(());
         }
@@ -343,7 +638,7 @@ impl<'a> StateManager<'a> {
             if tokens.len() == 1 {
                 if tokens[0] == "yes" {
                     self.clear_error()?;
-                    self.remove_dir(DirQuery::Disambiguated(self.dir_to_remove.clone().unwrap()), "")?;
+                    self.remove_dir(DirQuery::Disambiguated(self.dir_to_remove.clone().unwrap()), OsStr::new(PERMANENT_FLAG))?;
                     return Ok(());
                 } else if tokens[0] == "no" {
                     self.clear_error()?;
@@ -354,31 +649,47 @@ impl<'a> StateManager<'a> {
                 }
             }
             self.print_error("Input either 'yes' or 'no' for removal of directory and its contents")?;
+            return Ok(());
+        }
+
+        if self.confirmation.is_some() {
+            if tokens.len() == 1 && (tokens[0] == "yes" || tokens[0] == "no") {
+                let confirmation = self.confirmation.take().unwrap();
+                self.clear_error()?;
+                if tokens[0] == "yes" {
+                    self.carry_out(confirmation)?;
+                } else {
+                    self.end_confirmation()?;
+                }
+                return Ok(());
+            }
+            self.print_error("Input either 'yes' or 'no' to confirm")?;
+            return Ok(());
         }
 
         // this condition is true if there is disambiguation needed
         if self.command_buf.is_some() {
             // expect a single number for disambiguation or 'cancel'
             if tokens.len() == 1 {
-                if let Ok(num) = tokens[0].parse::<usize>() {
+                if let Some(num) = tokens[0].to_str().and_then(|t| t.parse::<usize>().ok()) {
                     if self.ambiguous_dirs.get(num).is_some() && self.command_buf.is_some() {
                         self.clear_error()?;
 
-                        let command = self.command_buf.as_ref().unwrap();
+                        let (procedure, command_string) = self.command_buf.take().unwrap();
                         let unambiguous_dir = self.ambiguous_dirs[num].clone();
-                        let command_string = command.1.clone();
                         
                         self.ambiguous_dirs.clear();
 
                         // execute the buffered command with the now disambiguated directory
-                        command.0(self, DirQuery::Disambiguated(unambiguous_dir), &command_string)?;
-                        self.command_buf = None;
+                        procedure(self, DirQuery::Disambiguated(unambiguous_dir), &command_string)?;
+                        self.clear_selection()?;
                         return Ok(());
                     }
                 } else if tokens[0] == "cancel" {
                     self.clear_error()?;
                     self.ambiguous_dirs.clear();
                     self.command_buf = None;
+                    self.clear_selection()?;
                     self.refresh_area(true, false)?;
                     return Ok(());
                 }
@@ -388,20 +699,178 @@ impl<'a> StateManager<'a> {
             return Ok(());
         }
 
-        match tokens[0] {
+        // a preview of renames which could not be made is only shown until the next command
+        self.discard_rename_plan()?;
+
+        match tokens[0].to_str().unwrap_or("") {
             // enter <directory>
+            // enter #N
+            // a search result is entered directly if it is a directory, otherwise the directory
+            // containing it is entered
             "enter" => {
                 if tokens.len() == 2 {
-                    self.enter_dir(DirQuery::ByName(tokens[1].to_string()), "")?;
+                    match self.find_result(&tokens[1]) {
+                        Some(Ok(path)) => self.enter_find_result(&path)?,
+                        Some(Err(message)) => self.print_error(&message)?,
+                        None => self.enter_dir(DirQuery::ByName(tokens[1].clone()), OsStr::new(""))?,
+                    }
+                } else {
+                    self.print_error("Usage: enter <directory|#N>")?;
+                }
+            },
+
+            // find <pattern> [--type file|dir|link] [--size +N|-N] [--age +N|-N]
+            // find
+            // searches the current directory and everything below it; without arguments a search
+            // which is still running is stopped, or else the results of the last search are shown
+            // again (or hidden)
+            "find" => {
+                if tokens.len() >= 2 {
+                    self.find(&tokens[1..])?;
+                } else if self.search.is_some() {
+                    self.stop_search()?;
+                } else if self.contents_view == ContentsView::FindResults {
+                    self.contents_view = ContentsView::Directory;
+                    self.contents_cursor = None;
+                    self.dir_contents.curr_pos = Vector2 { x: 0, y: 0 };
+                    self.refresh_area(false, true)?;
+                } else if self.find_results.is_some() {
+                    self.show_find_results()?;
+                } else {
+                    self.print_error("Usage: find <pattern> [--type file|dir|link] [--size +N|-N] [--age +N|-N]")?;
+                }
+            },
+
+            // grep <expression> [--ignore-case] [--max-size N]
+            // grep
+            // searches the contents of the files in the current directory and everything below it;
+            // without arguments a search which is still running is stopped
+            "grep" => {
+                if tokens.len() >= 2 {
+                    self.grep(&tokens[1..])?;
+                } else if self.search.is_some() {
+                    self.stop_search()?;
+                } else {
+                    self.print_error("Usage: grep <expression> [--ignore-case] [--max-size N]")?;
+                }
+            },
+
+            // view <file|directory|#N> [text|hex|meta]
+            // view
+            // shows a preview of a file; without arguments the preview is hidden again
+            "view" => {
+                if tokens.len() == 2 || tokens.len() == 3 {
+                    self.view(&tokens[1..])?;
+                } else if tokens.len() == 1 && self.contents_view == ContentsView::Preview {
+                    self.contents_view = ContentsView::Directory;
+                    self.dir_contents.curr_pos = Vector2 { x: 0, y: 0 };
+                    self.refresh_area(false, true)?;
+                } else {
+                    self.print_error("Usage: view <file|directory|#N> [text|hex|meta]")?;
+                }
+            },
+
+            // preview-pane on|off
+            // opens (or closes) a third pane on the right which shows the preview
+            "preview-pane" => {
+                match (tokens.len(), tokens.get(1).and_then(|t| t.to_str())) {
+                    (2, Some("on")) => self.set_preview_pane(true)?,
+                    (2, Some("off")) => self.set_preview_pane(false)?,
+                    _ => self.print_error("Usage: preview-pane on|off")?,
+                }
+            },
+
+            // edit <file|#N>
+            // opens a file in $VISUAL or $EDITOR
+            "edit" => {
+                if tokens.len() == 2 {
+                    self.edit(&tokens[1])?;
+                } else {
+                    self.print_error("Usage: edit <file|#N>")?;
+                }
+            },
+
+            // page <file|#N>
+            // shows a file in $PAGER
+            "page" => {
+                if tokens.len() == 2 {
+                    self.page(&tokens[1])?;
+                } else {
+                    self.print_error("Usage: page <file|#N>")?;
+                }
+            },
+
+            // open-with <program> <file|#N>
+            "open-with" => {
+                if tokens.len() == 3 {
+                    self.open_with(&tokens[1], &tokens[2])?;
+                } else {
+                    self.print_error("Usage: open-with <program> <file|#N>")?;
+                }
+            },
+
+            // shell
+            // starts a shell in the current directory; DirMan comes back when it exits
+            "shell" => {
+                if tokens.len() == 1 {
+                    self.shell()?;
+                } else {
+                    self.print_error("Usage: shell")?;
+                }
+            },
+
+            // sort <name|size|mtime|ext> [asc|desc] [--natural] [--ignore-case]
+            // changes the order of the contents panel
+            "sort" => {
+                if tokens.len() >= 2 && tokens.len() <= 5 {
+                    self.sort(&tokens[1..])?;
+                } else {
+                    self.print_error("Usage: sort <name|size|mtime|ext> [asc|desc] [--natural] [--ignore-case]")?;
+                }
+            },
+
+            // filter <pattern>
+            // filter
+            // only lists the entries of the contents panel whose names match a pattern; without
+            // arguments every entry is listed again
+            "filter" => {
+                if tokens.len() <= 2 {
+                    self.filter(tokens.get(1).map(OsString::as_os_str))?;
+                } else {
+                    self.print_error("Usage: filter [pattern]")?;
+                }
+            },
+
+            // reload-config
+            // reads the configuration file again and applies it
+            "reload-config" => {
+                if tokens.len() == 1 {
+                    self.reload_config()?;
+                } else {
+                    self.print_error("Usage: reload-config")?;
+                }
+            },
+
+            // errors
+            // toggles the list of entries that could not be read in the contents panel
+            "errors" => {
+                if tokens.len() == 1 {
+                    self.contents_view = if self.contents_view == ContentsView::Errors {
+                        ContentsView::Directory
+                    } else {
+                        ContentsView::Errors
+                    };
+                    self.dir_contents.curr_pos = Vector2 { x: 0, y: 0 };
+                    self.refresh_area(false, true)?;
                 } else {
-                    self.print_error("Usage: enter <directory>")?;
+                    self.print_error("Usage: errors")?;
                 }
             },
 
             // open <directory>
             "open" => {
                 if tokens.len() == 2 {
-                    self.open_dir(DirQuery::ByName(tokens[1].to_string()), "")?;
+                    self.open_dir(DirQuery::ByName(tokens[1].clone()), OsStr::new(""))?;
                 } else {
                     self.print_error("Usage: open <directory>")?;
                 }
@@ -410,52 +879,167 @@ impl<'a> StateManager<'a> {
             // close <directory>
             "close" => {
                 if tokens.len() == 2 {
-                    self.close_dir(DirQuery::ByName(tokens[1].to_string()), "")?;
+                    self.close_dir(DirQuery::ByName(tokens[1].clone()), OsStr::new(""))?;
                 } else {
                     self.print_error("Usage: close <directory>")?;
                 }
             },
 
-            // move <file> <directory>
+            // move <file|directory|pattern|#N> <directory|#N>
+            // move <directory|#N>
+            // the file or directory to move is in the current directory (or a search result);
+            // without one the marked entries are moved
             "move" => {
-                if tokens.len() == 3 {
-                    if self.curr_dir.borrow().files.iter().any(|e| e.borrow().name == tokens[1]) {
-                        self.move_to_dir(DirQuery::ByName(tokens[2].to_string()), tokens[1])?;
+                if tokens.len() == 2 {
+                    if !self.has_marks() {
+                        self.print_error("No entries are marked; use 'move <file|directory> <directory>'")?;
+                    } else if let Some(dir) = self.dir_argument(&tokens[1])? {
+                        self.move_batch_to_dir(dir, OsStr::new(""))?;
+                    }
+                } else if tokens.len() == 3 {
+                    let name = &tokens[1];
+                    let dir = match self.dir_argument(&tokens[2])? {
+                        Some(dir) => dir,
+                        None => return Ok(()),
+                    };
+                    if self.curr_dir.borrow().files.iter().any(|e| e.borrow().name == *name) {
+                        self.move_to_dir(dir, name)?;
+                    } else if self.curr_dir.borrow().directories.iter().any(|e| e.borrow().name == *name) {
+                        self.move_dir_to_dir(dir, name)?;
+                    } else if let Some(result) = self.find_result(name) {
+                        match result {
+                            Ok(path) => self.use_find_result(path, Self::move_batch_to_dir, dir)?,
+                            Err(message) => self.print_error(&message)?,
+                        }
+                    } else if self.select_pattern(name)? {
+                        if let Some(selection) = &self.selection {
+                            if self.config.confirm.pattern {
+                                let question = format!("Move {} to '{}'? yes/no",
+                                    marks::entry_count(selection.len()), names::display_name(&tokens[2]));
+                                self.preview_selection(&question)?;
+                                self.confirmation = Some(Confirmation::MoveSelection(dir));
+                            } else {
+                                self.carry_out(Confirmation::MoveSelection(dir))?;
+                            }
+                        }
                     } else {
-                        self.print_error("File attempted to be moved does not exist")?;
+                        self.print_error("File or directory attempted to be moved does not exist")?;
                     }
                 } else {
-                    self.print_error("Usage: move <file> <directory>")?;
+                    self.print_error("Usage: move [<file|directory|#N>] <directory|#N>")?;
                 }
             },
 
-            // copy <file> <directory>
+            // copy <file|directory|pattern|#N> <directory|#N>
+            // copy <directory|#N>
+            // the file or directory to copy is in the current directory (or a search result);
+            // without one the marked entries are copied
             "copy" => {
-                if tokens.len() == 3 {
-                    if self.curr_dir.borrow().files.iter().any(|e| e.borrow().name == tokens[1]) {
-                        self.copy_to_dir(DirQuery::ByName(tokens[2].to_string()), tokens[1])?;
+                if tokens.len() == 2 {
+                    if !self.has_marks() {
+                        self.print_error("No entries are marked; use 'copy <file|directory> <directory>'")?;
+                    } else if let Some(dir) = self.dir_argument(&tokens[1])? {
+                        self.copy_batch_to_dir(dir, OsStr::new(""))?;
+                    }
+                } else if tokens.len() == 3 {
+                    let name = &tokens[1];
+                    let dir = match self.dir_argument(&tokens[2])? {
+                        Some(dir) => dir,
+                        None => return Ok(()),
+                    };
+                    let maybe_file = self.curr_dir.borrow().files.iter().find(|e| e.borrow().name == *name).cloned();
+                    let maybe_dir = self.curr_dir.borrow().directories.iter().find(|e| e.borrow().name == *name).cloned();
+                    if let Some(file) = maybe_file {
+                        // the contents of the file need to be read to copy it
+                        if let Some(error) = &file.borrow().error {
+                            self.print_error(&format!("Cannot copy '{}': {}", names::display_name(name), error))?;
+                            return Ok(());
+                        }
+                        self.copy_to_dir(dir, name)?;
+                    } else if let Some(source_dir) = maybe_dir {
+                        // the whole subtree needs to be read to copy it
+                        if let Some(error) = Directory::find_error(&source_dir) {
+                            self.print_error(&format!("Cannot copy '{}': {}", names::display_name(name), error))?;
+                            return Ok(());
+                        }
+                        self.copy_dir_to_dir(dir, name)?;
+                    } else if let Some(result) = self.find_result(name) {
+                        match result {
+                            Ok(path) => self.use_find_result(path, Self::copy_batch_to_dir, dir)?,
+                            Err(message) => self.print_error(&message)?,
+                        }
+                    } else if self.select_pattern(name)? {
+                        // copying does not lose anything, so it goes ahead without asking
+                        if self.selection.is_some() {
+                            self.copy_batch_to_dir(dir, OsStr::new(""))?;
+                            if self.command_buf.is_none() {
+                                self.clear_selection()?;
+                            }
+                        }
                     } else {
-                        self.print_error("File attempted to be copied does not exist")?;
+                        self.print_error("File or directory attempted to be copied does not exist")?;
                     }
                 } else {
-                    self.print_error("Usage: copy <file> <directory>")?;
+                    self.print_error("Usage: copy [<file|directory|#N>] <directory|#N>")?;
+                }
+            },
+
+            // rename-batch <expression> <replacement>
+            // renames the entries of the current directory matching a regular expression, after
+            // showing the new names and asking for confirmation
+            "rename-batch" => {
+                if tokens.len() == 3 {
+                    if self.plan_rename_batch(&tokens[1], &tokens[2])? {
+                        if self.config.confirm.rename_batch {
+                            self.confirmation = Some(Confirmation::RenameBatch);
+                        } else {
+                            self.carry_out(Confirmation::RenameBatch)?;
+                        }
+                    }
+                } else {
+                    self.print_error("Usage: rename-batch <expression> <replacement>")?;
                 }
             },
 
             // rename <file|directory> <new_name>
+            // rename <name_template>
+            // without a file or directory the marked entries are renamed after the template
             "rename" => {
-                if tokens.len() == 3 {
+                if tokens.len() == 2 {
+                    if self.has_marks() {
+                        self.rename_marked(&tokens[1])?;
+                    } else {
+                        self.print_error("No entries are marked; use 'rename <file|directory> <new_name>'")?;
+                    }
+                } else if tokens.len() == 3 {
+                    let old_name = &tokens[1];
+                    let new_name = &tokens[2];
+                    if !is_valid_name(new_name) {
+                        self.print_error("New name must not be empty or contain path separators")?;
+                        return Ok(());
+                    }
+
                     let maybe_old_file_index: Option<usize> = self.curr_dir
                         .clone()
                         .borrow()
                         .files.iter()
-                        .position(|e| e.borrow().name == tokens[1]);
+                        .position(|e| e.borrow().name == *old_name);
 
                     if let Some(old_file_index) = maybe_old_file_index {
                         let mut new_path = self.curr_dir.borrow().full_path.clone();
-                        new_path.push(tokens[2]);
+                        new_path.push(new_name);
+                        // renaming would silently replace the other file, which could not be undone
+                        if new_path.symlink_metadata().is_ok() {
+                            self.print_error("File or directory with this name already exists")?;
+                            return Ok(());
+                        }
 
-                        fs::rename(&self.curr_dir.borrow().files[old_file_index].borrow().full_path, &new_path)?;
+                        let old_path = self.curr_dir.borrow().files[old_file_index].borrow().full_path.clone();
+                        fs::rename(&old_path, &new_path)?;
+                        self.journal.record(
+                            format!("rename '{}' to '{}'", names::display_name(old_name), names::display_name(new_name)),
+                            vec![journal::Change::Moved { from: old_path, to: new_path.clone() }],
+                        );
 
                         // remove the file from the tree and add the new one
                         self.curr_dir.borrow_mut().files.remove(old_file_index);
@@ -463,10 +1047,10 @@ impl<'a> StateManager<'a> {
 
                         self.refresh_area(false, true)?;
                     } else {
-                        self.rename_dir(DirQuery::ByName(tokens[1].to_string()), tokens[2])?;
+                        self.rename_dir(DirQuery::ByName(old_name.clone()), new_name)?;
                     }
                 } else {
-                    self.print_error("Usage: rename <file|directory> <new_name>")?;
+                    self.print_error("Usage: rename <file|directory> <new_name>, or rename <name_template> for the marked entries")?;
                 }
             },
 
@@ -474,25 +1058,33 @@ impl<'a> StateManager<'a> {
             // creates a new file or directory in the current directory
             "new" => {
                 if tokens.len() == 3 {
-                    let which = tokens[1];
-                    if which == "file" || which == "directory" {
+                    let which = &tokens[1];
+                    if !is_valid_name(&tokens[2]) {
+                        self.print_error("Name must not be empty or contain path separators")?;
+                    } else if which == "file" || which == "directory" {
                         let mut new_path = self.curr_dir.borrow().full_path.clone();
-                        new_path.push(tokens[2]);
+                        new_path.push(&tokens[2]);
                         if !new_path.exists() {
+                            let change = self.journal.created(new_path.clone())?;
                             if which == "file" {
                                 fs::File::create(&new_path)?;
                                 Self::add_item_to_tree(self.curr_dir.clone(), new_path);
                             } else {
                                 fs::create_dir(&new_path)?;
-                                let new_dir = Rc::new(RefCell::new(
-                                    Directory::new(new_path, vec![], vec![], Some(self.curr_dir.clone())))
-                                );
+                                let mut new_dir = Directory::new(new_path, Some(self.curr_dir.clone()));
+                                // nothing to read in a directory that was just created
+                                new_dir.loaded = true;
+                                let new_dir = Rc::new(RefCell::new(new_dir));
 
                                 self.curr_dir.borrow_mut().directories.push(new_dir);
                                 self.curr_dir.borrow_mut().directories.sort_by(|x, y| x.borrow().name.cmp(&y.borrow().name));
 
                                 self.refresh_area(true, false)?;
                             }
+                            self.journal.record(
+                                format!("new {} '{}'", which.to_string_lossy(), names::display_name(&tokens[2])),
+                                vec![change],
+                            );
                             self.refresh_area(false, true)?;
                         } else {
                             self.print_error("File or directory with this name already exists")?;
@@ -503,22 +1095,109 @@ impl<'a> StateManager<'a> {
                 }
             },
 
-            // remove <file|directory>
+            // remove [--permanent] [<file|directory|pattern>]
+            // entries are moved to the trash unless --permanent is given; without a file or
+            // directory the marked entries are removed
             "remove" => {
-                if tokens.len() == 2 {
-                    let maybe_file_index = self.curr_dir.borrow().files.iter().position(|e| e.borrow().name == tokens[1]);
+                let permanent = tokens.len() >= 2 && tokens[1] == PERMANENT_FLAG;
+                if tokens.len() == 1 || (tokens.len() == 2 && permanent) {
+                    if !self.has_marks() {
+                        self.print_error("No entries are marked; use 'remove <file|directory>'")?;
+                    } else if permanent && self.config.confirm.permanent_remove {
+                        self.print_error("The marked entries will be permanently removed. Continue? yes/no")?;
+                        self.confirmation = Some(Confirmation::RemoveMarked);
+                    } else if permanent {
+                        self.carry_out(Confirmation::RemoveMarked)?;
+                    } else {
+                        self.remove_batch(false)?;
+                    }
+                } else if tokens.len() == 2 || (tokens.len() == 3 && permanent) {
+                    let file_name = tokens.last().unwrap();
+                    let maybe_file_index = self.curr_dir.borrow().files.iter().position(|e| e.borrow().name == *file_name);
                     if let Some(file_index) = maybe_file_index {
-                        fs::remove_file(&self.curr_dir.borrow().files[file_index].borrow().full_path)?;
-                        self.curr_dir.borrow_mut().files.remove(file_index);
-                        self.refresh_area(false, true)?;
+                        self.remove_file(file_index, permanent)?;
+                    } else if self.curr_dir.borrow().directories.iter().all(|d| d.borrow().name != *file_name)
+                        && self.select_pattern(file_name)? {
+                        if let Some(selection) = &self.selection {
+                            let question = if permanent {
+                                format!("Permanently remove {}? yes/no", marks::entry_count(selection.len()))
+                            } else {
+                                format!("Move {} to the trash? yes/no", marks::entry_count(selection.len()))
+                            };
+                            if self.config.confirm.pattern || (permanent && self.config.confirm.permanent_remove) {
+                                self.preview_selection(&question)?;
+                                self.confirmation = Some(Confirmation::RemoveSelection { permanent });
+                            } else {
+                                self.carry_out(Confirmation::RemoveSelection { permanent })?;
+                            }
+                        }
                     } else {
-                        self.remove_dir(DirQuery::ByName(tokens[1].to_string()), "")?;
+                        let mode = if permanent { PERMANENT_FLAG } else { "" };
+                        self.remove_dir(DirQuery::ByName(file_name.clone()), OsStr::new(mode))?;
                     }
                 } else {
-                    self.print_error("Usage: remove <file|directory>")?;
+                    self.print_error("Usage: remove [--permanent] [<file|directory|pattern>]")?;
                 }
             }
 
+            // trash list|restore <name>|empty
+            "trash" => {
+                if self.trash.is_none() {
+                    self.print_error("The trash is not available; use 'remove --permanent' to remove permanently")?;
+                    return Ok(());
+                }
+                match (tokens.len(), tokens.get(1).and_then(|t| t.to_str())) {
+                    // toggles the list of entries in the trash in the contents panel
+                    (2, Some("list")) => {
+                        self.contents_view = if self.contents_view == ContentsView::Trash {
+                            ContentsView::Directory
+                        } else {
+                            ContentsView::Trash
+                        };
+                        self.dir_contents.curr_pos = Vector2 { x: 0, y: 0 };
+                        self.refresh_area(false, true)?;
+                    },
+                    (3, Some("restore")) => self.restore_from_trash(&tokens[2])?,
+                    (2, Some("empty")) if self.config.confirm.empty_trash => {
+                        self.print_error("Everything in the trash will be permanently removed. Continue? yes/no")?;
+                        self.confirmation = Some(Confirmation::EmptyTrash);
+                    },
+                    (2, Some("empty")) => self.carry_out(Confirmation::EmptyTrash)?,
+                    _ => self.print_error("Usage: trash list|restore <name>|empty")?,
+                }
+            },
+
+            // mark <name|pattern|all>
+            // unmark <name|pattern|all>
+            // marks entries of the current directory for the batch forms of move, copy, remove and rename
+            "mark" | "unmark" => {
+                if tokens.len() == 2 {
+                    self.set_marks(&tokens[1], tokens[0] == "mark")?;
+                } else {
+                    self.print_error(&format!("Usage: {} <name|pattern|all>", tokens[0].to_string_lossy()))?;
+                }
+            },
+
+            // undo
+            // reverts the most recent change made by a command
+            "undo" => {
+                if tokens.len() == 1 {
+                    self.replay_journal(true)?;
+                } else {
+                    self.print_error("Usage: undo")?;
+                }
+            },
+
+            // redo
+            // makes the most recently undone change again
+            "redo" => {
+                if tokens.len() == 1 {
+                    self.replay_journal(false)?;
+                } else {
+                    self.print_error("Usage: redo")?;
+                }
+            },
+
             _ => {
                 self.print_error("Invalid command")?;
             },
@@ -536,13 +1215,14 @@ impl<'a> StateManager<'a> {
     fn get_copy_path(original: PathBuf) -> PathBuf {
         if original.exists() {
             for i in 1.. {
-                let mut s = String::new();
+                let mut s = OsString::new();
                 if let Some(file_stem) = original.file_stem() {
-                    s.push_str(file_stem.to_str().unwrap());
+                    s.push(file_stem);
                 }
-                s.push_str(&format!("_{}", i));
+                s.push(format!("_{}", i));
                 if let Some(ext) = original.extension() {
-                    s.push_str(&format!(".{}", ext.to_str().unwrap()));
+                    s.push(".");
+                    s.push(ext);
                 }
 
                 let mut copy = original.parent().unwrap().to_path_buf();
@@ -557,19 +1237,270 @@ impl<'a> StateManager<'a> {
 
     // adds a file to the in-memory directory tree data structure
     fn add_item_to_tree(dir: DirectoryRef, path: PathBuf) {
+        // a directory which has not been read yet will pick up the file once it is read
+        if !dir.borrow().loaded {
+            return;
+        }
+
         // files/dirs are kept sorted
         let files = &mut dir.borrow_mut().files;
         files.push(Rc::new(RefCell::new(File::new(path))));
         files.sort_by(|x, y| x.borrow().name.cmp(&y.borrow().name));
     }
 
+    // reads the contents of a directory right away if it has not been loaded yet
+    fn ensure_loaded(&mut self, dir: &DirectoryRef) -> io::Result<()> {
+        if !dir.borrow().loaded {
+            let entries = scan::read_entries(&dir.borrow().full_path, self.show_hidden);
+            match entries {
+                Ok(entries) => {
+                    Directory::apply_entries(dir, entries);
+                    self.queue_scans(dir);
+                },
+                Err(error) => Directory::set_unreadable(dir, &error),
+            }
+        }
+        Ok(())
+    }
+
+    // queues the unloaded child directories of a directory to be read in the background;
+    // directories which are closed or past the depth limit are left for when they are opened
+    fn queue_scans(&mut self, dir: &DirectoryRef) {
+        if self.closed_dirs.contains(dir) {
+            return;
+        }
+
+        let depth = dir.borrow().depth() + 1;
+        if self.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            return;
+        }
+
+        for child in &dir.borrow().directories {
+            if self.closed_dirs.contains(child) {
+                continue;
+            }
+
+            let child = child.borrow();
+            if !child.loaded && !self.pending_scans.contains(&child.full_path) {
+                self.pending_scans.insert(child.full_path.clone());
+                self.scanner.request(child.full_path.clone());
+            }
+        }
+    }
+
+    // queues unloaded directories anywhere in the subtree of a directory to be read; used when
+    // directories are moved, as anything queued before was queued under the old paths
+    fn queue_subtree_scans(&mut self, dir: &DirectoryRef) {
+        self.queue_scans(dir);
+        let children = dir.borrow().directories.clone();
+        for child in children.iter().filter(|c| c.borrow().loaded) {
+            self.queue_subtree_scans(child);
+        }
+    }
+
+    // adds any directories finished by the background scan to the tree; returns whether
+    // anything was drawn
+    fn apply_scan_results(&mut self) -> io::Result<bool> {
+        // limits so that input stays responsive while a huge tree is being read
+        const MAX_RESULTS: usize = 500;
+        const REDRAW_EVERY: Duration = Duration::from_millis(200);
+
+        for _ in 0..MAX_RESULTS {
+            let result = match self.scanner.try_result() {
+                Some(result) => result,
+                None => break,
+            };
+            self.pending_scans.remove(&result.path);
+
+            // the directory may have been removed, renamed or loaded in the meantime
+            let dir = match self.find_loaded_dir(&result.path) {
+                Some(dir) if !dir.borrow().loaded => dir,
+                _ => continue,
+            };
+
+            match result.entries {
+                Ok(entries) => {
+                    Directory::apply_entries(&dir, entries);
+                    self.queue_scans(&dir);
+                },
+                Err(error) => Directory::set_unreadable(&dir, &error),
+            }
+            self.scan_redraw_needed = true;
+        }
+
+        // the whole tree is rebuilt when redrawn, so do not redraw for every directory read
+        if self.scan_redraw_needed && self.last_scan_redraw.elapsed() >= REDRAW_EVERY {
+            self.scan_redraw_needed = false;
+            self.last_scan_redraw = Instant::now();
+            self.refresh_area(true, false)?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    // finds the directory in the tree with the given path, if it (and all of its parents)
+    // have been loaded
+    fn find_loaded_dir(&self, path: &Path) -> Option<DirectoryRef> {
+        let relative = path.strip_prefix(&self.root.borrow().full_path).ok()?;
+
+        let mut dir = self.root.clone();
+        for component in relative.components() {
+            let next = dir.borrow().directories.iter()
+                .find(|d| d.borrow().name == component.as_os_str())
+                .cloned()?;
+            dir = next;
+        }
+        Some(dir)
+    }
+
+    // finds the directory in the tree with the given path, reading the directories on the way to
+    // it which have not been read yet (e.g. for a search result in a closed directory)
+    fn load_dir_path(&mut self, path: &Path) -> io::Result<Option<DirectoryRef>> {
+        let relative = match path.strip_prefix(&self.root.borrow().full_path) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => return Ok(None),
+        };
+
+        let mut dir = self.root.clone();
+        for component in relative.components() {
+            self.ensure_loaded(&dir)?;
+            let next = dir.borrow().directories.iter()
+                .find(|d| d.borrow().name == component.as_os_str())
+                .cloned();
+            match next {
+                Some(next) => dir = next,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(dir))
+    }
+
+    // undoes or redoes a change from the journal and updates the tree to match
+    fn replay_journal(&mut self, undo: bool) -> io::Result<()> {
+        let replayed = if undo { self.journal.undo()? } else { self.journal.redo()? };
+        let replayed = match replayed {
+            Some(replayed) => replayed,
+            None => {
+                self.print_error(if undo { "Nothing to undo" } else { "Nothing to redo" })?;
+                return Ok(());
+            },
+        };
+
+        for (from, to) in &replayed.moves {
+            self.relocate_in_tree(from, to);
+        }
+        self.forget_detached_dirs();
+        self.refresh_area(true, true)?;
+
+        let verb = if undo { "Undid" } else { "Redid" };
+        self.print_message(&format!("{}: {}", verb, replayed.description))?;
+        Ok(())
+    }
+
+    // updates the tree after a file or directory was moved on disk; either location may be
+    // outside of the tree (e.g. in the journal's holding area)
+    fn relocate_in_tree(&mut self, from: &Path, to: &Path) {
+        let moved_dir = self.take_from_tree(from);
+        self.put_in_tree(to, moved_dir);
+    }
+
+    // removes the entry at a path from the tree; if it is a directory its node is returned so
+    // that it can be put back elsewhere without reading its contents again
+    fn take_from_tree(&mut self, path: &Path) -> Option<DirectoryRef> {
+        let parent = path.parent().and_then(|p| self.find_loaded_dir(p))?;
+        let name = path.file_name().unwrap();
+        let mut parent = parent.borrow_mut();
+        parent.files.retain(|f| f.borrow().name != name);
+        let index = parent.directories.iter().position(|d| d.borrow().name == name)?;
+        Some(parent.directories.remove(index))
+    }
+
+    // adds the entry at a path to the tree (if its directory is loaded); dir is the node to use
+    // if it is a directory which was in the tree before
+    fn put_in_tree(&mut self, path: &Path, dir: Option<DirectoryRef>) {
+        let parent = match path.parent().and_then(|p| self.find_loaded_dir(p)) {
+            Some(parent) if parent.borrow().loaded => parent,
+            _ => return,
+        };
+
+        // symbolic links to directories are shown as files, the same as when they are read
+        if path.symlink_metadata().is_ok_and(|m| m.is_dir()) {
+            let dir = match dir {
+                Some(dir) => {
+                    dir.borrow_mut().parent = Some(parent.clone());
+                    Directory::set_path(&dir, path.to_path_buf());
+                    dir
+                },
+                None => Rc::new(RefCell::new(Directory::new(path.to_path_buf(), Some(parent.clone())))),
+            };
+            parent.borrow_mut().directories.push(dir.clone());
+            parent.borrow_mut().directories.sort_by(|x, y| x.borrow().name.cmp(&y.borrow().name));
+            self.queue_scans(&parent);
+            self.queue_subtree_scans(&dir);
+        } else {
+            Self::add_item_to_tree(parent, path.to_path_buf());
+        }
+    }
+
+    // after directories have been taken out of the tree, moves the current directory up to one
+    // which is still in the tree and forgets closed directories which are gone
+    fn forget_detached_dirs(&mut self) {
+        let mut curr_dir = self.curr_dir.clone();
+        while !self.is_in_tree(&curr_dir) {
+            let parent = curr_dir.borrow().parent.clone().unwrap();
+            curr_dir = parent;
+        }
+        if !Rc::ptr_eq(&curr_dir, &self.curr_dir) {
+            self.curr_dir = curr_dir;
+            self.dir_contents.curr_pos = Vector2 { x: 0, y: 0 };
+        }
+
+        let closed_dirs = self.closed_dirs.iter().filter(|dir| self.is_in_tree(dir)).cloned().collect();
+        self.closed_dirs = closed_dirs;
+    }
+
+    // whether a directory is still part of the tree (rather than having been taken out of it)
+    fn is_in_tree(&self, dir: &DirectoryRef) -> bool {
+        self.find_loaded_dir(&dir.borrow().full_path).is_some_and(|found| Rc::ptr_eq(&found, dir))
+    }
+
+    // moves a file or directory to the trash and records it in the journal; prints an error
+    // and returns false if there is no trash
+    fn move_to_trash(&mut self, path: PathBuf, name: &OsStr) -> io::Result<bool> {
+        let change = match &self.trash {
+            Some(trash) => trash.put(path)?,
+            None => {
+                self.print_error("The trash is not available; use 'remove --permanent' to remove permanently")?;
+                return Ok(false);
+            },
+        };
+        self.journal.record(format!("remove '{}'", names::display_name(name)), vec![change]);
+        Ok(true)
+    }
+
+    // moves an entry in the trash back to where it was removed from
+    fn restore_from_trash(&mut self, name: &OsStr) -> io::Result<()> {
+        let (trashed, original) = self.trash.as_ref().unwrap().restore(name)?;
+        self.relocate_in_tree(&trashed, &original);
+        self.refresh_area(true, true)?;
+        self.print_message(&format!("Restored '{}'", names::display_name(original.as_os_str())))?;
+        Ok(())
+    }
+
+    fn empty_trash(&mut self) -> io::Result<()> {
+        let count = self.trash.as_ref().unwrap().empty()?;
+        self.refresh_area(false, true)?;
+        self.print_message(&format!("Permanently removed {} entries from the trash", count))?;
+        Ok(())
+    }
+
     // +----------------------------------+
     // |   Bufferable command functions   |
     // +----------------------------------+
 
     // helper function for *_dir methods below; checks dir parameter and returns a concrete
     // Directory if there is no ambiguity or None if there is
-    fn get_dir(&mut self, func: CommandProcedure<'a>, dir_query: DirQuery, other_arg: &str)
+    fn get_dir(&mut self, func: CommandProcedure<'a>, dir_query: DirQuery, other_arg: &OsStr)
         -> io::Result<Option<DirectoryRef>>
     {
         match dir_query {
@@ -584,11 +1515,11 @@ impl<'a> StateManager<'a> {
                     self.ambiguous_dirs = possible_dirs;
                     self.refresh_area(true, false)?;
                     // buffer a command for disambiguation
-                    self.command_buf = Some((func, other_arg.to_string()));
+                    self.command_buf = Some((func, other_arg.to_os_string()));
                     Ok(None)
                 } else {
                     // if directory is unambiguous return it
-                    if let Some(d) = possible_dirs.get(0) {
+                    if let Some(d) = possible_dirs.first() {
                         Ok(Some(d.clone()))
                     } else {
                         self.print_error("Specified directory does not exist")?;
@@ -600,25 +1531,28 @@ impl<'a> StateManager<'a> {
     }
 
     // enter a directory to view its contents
-    fn enter_dir(&mut self, dir: DirQuery, other_arg: &str) -> io::Result<()> {
+    fn enter_dir(&mut self, dir: DirQuery, other_arg: &OsStr) -> io::Result<()> {
         if let Some(dir) = self.get_dir(Self::enter_dir, dir, other_arg)? {
+            self.ensure_loaded(&dir)?;
             self.curr_dir = dir;
+            self.contents_view = ContentsView::Directory;
             self.dir_contents.curr_pos = Vector2 { x: 0, y: 0 };
+            // marks only apply within the directory they were made in
+            self.marks.clear();
+            self.contents_cursor = None;
             self.refresh_area(true, true)?;
         }
         Ok(())
     }
 
     // hides the inner directories of an opened directory in the directory tree
-    fn close_dir(&mut self, dir: DirQuery, other_arg: &str) -> io::Result<()> {
+    fn close_dir(&mut self, dir: DirQuery, other_arg: &OsStr) -> io::Result<()> {
         if let Some(dir) = self.get_dir(Self::close_dir, dir, other_arg)? {
             self.closed_dirs.push(dir.clone());
 
             // if the current directory is a child of the closed directory then move
             // the current directory up to the closed one
-            if self.curr_dir.borrow().full_path.to_str().unwrap()
-               .contains(dir.borrow().full_path.to_str().unwrap())
-            {
+            if self.curr_dir.borrow().full_path.starts_with(&dir.borrow().full_path) {
                 self.curr_dir = dir;
                 self.dir_contents.curr_pos = Vector2 { x: 0, y: 0 };
             }
@@ -629,10 +1563,13 @@ impl<'a> StateManager<'a> {
     }
 
     // opens a closed directory in the directory tree
-    fn open_dir(&mut self, dir: DirQuery, other_arg: &str) -> io::Result<()> {
+    fn open_dir(&mut self, dir: DirQuery, other_arg: &OsStr) -> io::Result<()> {
         if let Some(dir) = self.get_dir(Self::open_dir, dir, other_arg)? {
             if let Some(index) = self.closed_dirs.iter().position(|e| *e == dir) {
                 self.closed_dirs.remove(index);
+                // closed directories are skipped by the background scan, so continue it from here
+                self.ensure_loaded(&dir)?;
+                self.queue_scans(&dir);
                 self.refresh_area(true, false)?;
             }
         }
@@ -640,14 +1577,18 @@ impl<'a> StateManager<'a> {
     }
 
     // moves a file into a different directory
-    fn move_to_dir(&mut self, dir: DirQuery, file_name: &str) -> io::Result<()> {
+    fn move_to_dir(&mut self, dir: DirQuery, file_name: &OsStr) -> io::Result<()> {
         if let Some(dir) = self.get_dir(Self::move_to_dir, dir, file_name)? {
             let mut file_path = self.curr_dir.borrow().full_path.clone();
             file_path.push(file_name);
             let mut new_path = dir.borrow().full_path.clone();
             new_path.push(file_name);
             new_path = Self::get_copy_path(new_path);
-            fs::rename(file_path, &new_path)?;
+            fs::rename(&file_path, &new_path)?;
+            self.journal.record(
+                format!("move '{}' to '{}'", names::display_name(file_name), names::display_name(&dir.borrow().name)),
+                vec![journal::Change::Moved { from: file_path, to: new_path.clone() }],
+            );
 
             // remove this file from the current directory
             let index = self.curr_dir.borrow().files
@@ -665,14 +1606,19 @@ impl<'a> StateManager<'a> {
     }
 
     // copies a file to a different directory
-    fn copy_to_dir(&mut self, dir: DirQuery, file_name: &str) -> io::Result<()> {
-        if let Some(dir) = self.get_dir(Self::move_to_dir, dir, file_name)? {
+    fn copy_to_dir(&mut self, dir: DirQuery, file_name: &OsStr) -> io::Result<()> {
+        if let Some(dir) = self.get_dir(Self::copy_to_dir, dir, file_name)? {
             let mut file_path = self.curr_dir.borrow().full_path.clone();
             file_path.push(file_name);
             let mut new_path = dir.borrow().full_path.clone();
             new_path.push(file_name);
             new_path = Self::get_copy_path(new_path);
+            let change = self.journal.created(new_path.clone())?;
             fs::copy(file_path, &new_path)?;
+            self.journal.record(
+                format!("copy '{}' to '{}'", names::display_name(file_name), names::display_name(&dir.borrow().name)),
+                vec![change],
+            );
 
             // add this file to its new directory
             Self::add_item_to_tree(dir, new_path);
@@ -682,8 +1628,89 @@ impl<'a> StateManager<'a> {
         Ok(())
     }
 
+    // moves a directory (and everything in it) from the current directory into a different directory
+    fn move_dir_to_dir(&mut self, dir: DirQuery, dir_name: &OsStr) -> io::Result<()> {
+        if let Some(dest) = self.get_dir(Self::move_dir_to_dir, dir, dir_name)? {
+            let source = match self.child_dir(dir_name) {
+                Some(source) => source,
+                None => return Ok(()),
+            };
+            if !self.check_dir_destination(&source, &dest, "move")? {
+                return Ok(());
+            }
+            if dest == self.curr_dir {
+                self.print_error("Directory is already in the destination directory")?;
+                return Ok(());
+            }
+
+            // the moved directory is added to the destination's contents, so they need to be known
+            self.ensure_loaded(&dest)?;
+
+            let mut new_path = dest.borrow().full_path.clone();
+            new_path.push(dir_name);
+            new_path = Self::get_copy_path(new_path);
+            let old_path = source.borrow().full_path.clone();
+            fsops::move_path(&old_path, &new_path)?;
+            self.journal.record(
+                format!("move '{}' to '{}'", names::display_name(dir_name), names::display_name(&dest.borrow().name)),
+                vec![journal::Change::Moved { from: old_path, to: new_path.clone() }],
+            );
+
+            // detach the directory from its old parent and attach it to the new one; the same
+            // Directory is kept so that the current directory and closed directories stay valid
+            self.curr_dir.borrow_mut().directories.retain(|e| e != &source);
+            source.borrow_mut().parent = Some(dest.clone());
+            Directory::set_path(&source, new_path);
+            dest.borrow_mut().directories.push(source.clone());
+            dest.borrow_mut().directories.sort_by(|x, y| x.borrow().name.cmp(&y.borrow().name));
+
+            self.queue_subtree_scans(&source);
+            self.refresh_area(true, true)?;
+        }
+
+        Ok(())
+    }
+
+    // recursively copies a directory from the current directory into a different directory
+    fn copy_dir_to_dir(&mut self, dir: DirQuery, dir_name: &OsStr) -> io::Result<()> {
+        if let Some(dest) = self.get_dir(Self::copy_dir_to_dir, dir, dir_name)? {
+            let source = match self.child_dir(dir_name) {
+                Some(source) => source,
+                None => return Ok(()),
+            };
+            if !self.check_dir_destination(&source, &dest, "copy")? {
+                return Ok(());
+            }
+
+            let mut new_path = dest.borrow().full_path.clone();
+            new_path.push(dir_name);
+            new_path = Self::get_copy_path(new_path);
+            let change = self.journal.created(new_path.clone())?;
+            fsops::copy_dir_all(&source.borrow().full_path, &new_path)?;
+            self.journal.record(
+                format!("copy '{}' to '{}'", names::display_name(dir_name), names::display_name(&dest.borrow().name)),
+                vec![change],
+            );
+
+            // the copy is read like any other new directory; it starts out closed if the original was
+            if dest.borrow().loaded {
+                let copy = Rc::new(RefCell::new(Directory::new(new_path, Some(dest.clone()))));
+                if self.closed_dirs.contains(&source) {
+                    self.closed_dirs.push(copy.clone());
+                }
+                dest.borrow_mut().directories.push(copy);
+                dest.borrow_mut().directories.sort_by(|x, y| x.borrow().name.cmp(&y.borrow().name));
+                self.queue_scans(&dest);
+            }
+
+            self.refresh_area(true, true)?;
+        }
+
+        Ok(())
+    }
+
     // renames a directory
-    fn rename_dir(&mut self, dir: DirQuery, new_name: &str) -> io::Result<()> {
+    fn rename_dir(&mut self, dir: DirQuery, new_name: &OsStr) -> io::Result<()> {
         if let Some(dir) = self.get_dir(Self::rename_dir, dir, new_name)? {
             // cannot rename root directory
             if dir.borrow().parent.is_none() {
@@ -694,10 +1721,22 @@ impl<'a> StateManager<'a> {
             let mut new_path = dir.borrow().full_path.parent().unwrap().to_path_buf();
             new_path.push(new_name);
 
-            fs::rename(&dir.borrow().full_path, &new_path)?;
+            // renaming would silently replace an empty directory, which could not be undone
+            if new_path.symlink_metadata().is_ok() {
+                self.print_error("File or directory with this name already exists")?;
+                return Ok(());
+            }
+
+            let old_path = dir.borrow().full_path.clone();
+            fs::rename(&old_path, &new_path)?;
+            self.journal.record(
+                format!("rename '{}' to '{}'", names::display_name(&dir.borrow().name), names::display_name(new_name)),
+                vec![journal::Change::Moved { from: old_path, to: new_path.clone() }],
+            );
 
-            dir.borrow_mut().name = OsString::from(new_name);
-            dir.borrow_mut().full_path = new_path;
+            Directory::set_path(&dir, new_path);
+            // anything queued for reading inside of the directory was queued under its old path
+            self.queue_subtree_scans(&dir);
 
             // re-sort directories
             if let Some(parent) = &dir.borrow().parent {
@@ -710,48 +1749,114 @@ impl<'a> StateManager<'a> {
         Ok(())
     }
 
-    // removes a directory
-    fn remove_dir(&mut self, dir: DirQuery, other_arg: &str) -> io::Result<()> {
-        if let Some(dir) = self.get_dir(Self::remove_dir, dir, other_arg)? {
+    // removes a file of the current directory, by its index, to the trash or permanently
+    fn remove_file(&mut self, index: usize, permanent: bool) -> io::Result<()> {
+        let (name, path) = {
+            let file = self.curr_dir.borrow().files[index].clone();
+            let file = file.borrow();
+            (file.name.clone(), file.full_path.clone())
+        };
+        if permanent {
+            fs::remove_file(&path)?;
+        } else if !self.move_to_trash(path, &name)? {
+            return Ok(());
+        }
+        self.curr_dir.borrow_mut().files.remove(index);
+        self.refresh_area(false, true)
+    }
+
+    // carries out an action which has been confirmed, or which the configuration does not ask
+    // to confirm
+    fn carry_out(&mut self, confirmation: Confirmation) -> io::Result<()> {
+        match confirmation {
+            Confirmation::EmptyTrash => self.empty_trash()?,
+            Confirmation::RemoveMarked => self.remove_batch(true)?,
+            Confirmation::MoveSelection(dir) => self.move_batch_to_dir(dir, OsStr::new(""))?,
+            Confirmation::RemoveSelection { permanent } => self.remove_batch(permanent)?,
+            Confirmation::RenameBatch => self.apply_rename_plan()?,
+        }
+        self.end_confirmation()
+    }
+
+    // drops what was shown for an action which was asked about
+    fn end_confirmation(&mut self) -> io::Result<()> {
+        self.discard_rename_plan()?;
+        // the entries are only needed further if the destination turned out to be ambiguous
+        if self.command_buf.is_none() {
+            self.clear_selection()?;
+        }
+        Ok(())
+    }
+
+    // removes a directory; it is moved to the trash unless mode is PERMANENT_FLAG, in which case
+    // it is deleted once the user confirms
+    fn remove_dir(&mut self, dir: DirQuery, mode: &OsStr) -> io::Result<()> {
+        if let Some(dir) = self.get_dir(Self::remove_dir, dir, mode)? {
             // cannot remove root directory
             if dir.borrow().parent.is_none() {
                 self.print_error("Cannot remove root directory")?;
                 return Ok(());
             }
 
-            if self.dir_to_remove.is_some() {
-                // dir_to_remove is just be dir at this point
-                fs::remove_dir_all(&dir.borrow().full_path)?;
-
-                let borrow = dir.borrow();
-                let parent = borrow.parent.as_ref().unwrap();
-
-                let index_to_remove = parent.borrow().directories.iter().position(|e| e == &dir).unwrap();
+            let dir_name = dir.borrow().name.clone();
+            if mode == PERMANENT_FLAG {
+                // a directory with unreadable contents could only be partially removed
+                if let Some(error) = Directory::find_error(&dir) {
+                    self.dir_to_remove = None;
+                    self.print_error(&format!("Cannot remove '{}': {}", names::display_name(&dir_name), error))?;
+                    return Ok(());
+                }
 
-                // if the current directory is a child of the closed directory then move
-                // the current directory up to the closed one
-                if self.curr_dir.borrow().full_path.to_str().unwrap()
-                   .contains(dir.borrow().full_path.to_str().unwrap())
-                {
-                    self.curr_dir = parent.clone();
-                    self.dir_contents.curr_pos = Vector2 { x: 0, y: 0 };
+                if self.dir_to_remove.is_none() && self.config.confirm.permanent_remove {
+                    self.print_error("The directory and all of its contents will be permanently removed. Continue? yes/no")?;
+                    self.dir_to_remove = Some(dir);
+                    self.refresh_area(true, true)?;
+                    return Ok(());
                 }
 
+                // dir_to_remove is just be dir at this point
                 self.dir_to_remove = None;
+                fs::remove_dir_all(&dir.borrow().full_path)?;
+            } else if !self.move_to_trash(dir.borrow().full_path.clone(), &dir_name)? {
+                return Ok(());
+            }
 
-                parent.borrow_mut().directories.remove(index_to_remove);
-                self.refresh_area(true, true)?;
-            } else {
-                self.print_error("The directory and all of its contents will be removed. Continue? yes/no")?;
-                self.dir_to_remove = Some(dir);
+            let borrow = dir.borrow();
+            let parent = borrow.parent.as_ref().unwrap();
+
+            let index_to_remove = parent.borrow().directories.iter().position(|e| e == &dir).unwrap();
+
+            // if the current directory is a child of the removed directory then move
+            // the current directory up to its parent
+            if self.curr_dir.borrow().full_path.starts_with(&dir.borrow().full_path) {
+                self.curr_dir = parent.clone();
+                self.dir_contents.curr_pos = Vector2 { x: 0, y: 0 };
             }
 
+            parent.borrow_mut().directories.remove(index_to_remove);
             self.refresh_area(true, true)?;
         }
 
         Ok(())
     }
 
+    // helpers for the directory copy/move commands above
+
+    // returns the child directory of the current directory with the given name
+    fn child_dir(&self, name: &OsStr) -> Option<DirectoryRef> {
+        self.curr_dir.borrow().directories.iter().find(|e| e.borrow().name == name).cloned()
+    }
+
+    // checks that a directory can be copied or moved into the destination; prints an error
+    // and returns false if it cannot
+    fn check_dir_destination(&mut self, source: &DirectoryRef, dest: &DirectoryRef, action: &str) -> io::Result<bool> {
+        if dest.borrow().full_path.starts_with(&source.borrow().full_path) {
+            self.print_error(&format!("Cannot {} a directory into itself", action))?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
     // +-----------------------------------------+
     // |   End of bufferable command functions   |
     // +-----------------------------------------+
@@ -760,11 +1865,39 @@ impl<'a> StateManager<'a> {
     fn print_error(&mut self, message: &str) -> io::Result<()> {
         self.term.move_cursor_to(0, 0)?;
         self.term.clear_line()?;
-        self.term.write_str(&format!("{}", message.color(Color::Red)))?;
+        self.term.write_str(&paint(&self.fit_message(message), Color::Error))?;
         self.error_message_active = true;
         Ok(())
     }
     
+    // prints an informational message to the top of the terminal window; it stays until
+    // the next command, the same as an error message
+    fn print_message(&mut self, message: &str) -> io::Result<()> {
+        self.term.move_cursor_to(0, 0)?;
+        self.term.clear_line()?;
+        self.term.write_str(&self.fit_message(message))?;
+        self.error_message_active = true;
+        Ok(())
+    }
+
+    // cuts a message short so that it stays on the top line instead of wrapping over the panels
+    fn fit_message(&self, message: &str) -> String {
+        let width = self.term.size().1 as usize;
+        if message.chars().count() <= width {
+            return message.to_string();
+        }
+        message.chars().take(width - 1).chain(std::iter::once('…')).collect()
+    }
+
+    // reports a failed command or action (e.g. permission denied) instead of exiting
+    fn report_failure(&mut self, result: io::Result<()>) -> io::Result<()> {
+        if let Err(error) = result {
+            self.print_error(&format!("Command failed: {}", scan::describe_error(&error)))?;
+            self.refresh_area(true, true)?;
+        }
+        Ok(())
+    }
+
     // clears error message (if there was one) and prints 'DirMan' at top of terminal window
     fn clear_error(&self) -> io::Result<()> {
         self.term.move_cursor_to(0, 0)?;
@@ -780,7 +1913,6 @@ impl<'a> StateManager<'a> {
     {
         let mut contents = vec![];
     
-        let curr_dir_name: String = curr_dir.borrow().name.clone().into_string().unwrap();
     
         // flags for what needs to be printed e.g. '<dir> +' and/or '<dir>: #' for closed/ambiguous
         let mut remove = false;
@@ -788,46 +1920,48 @@ impl<'a> StateManager<'a> {
         let mut ambiguous = false;
 
         // flag for which color to print the directory name in;
-        // precedence: current(blue) > remove(red) > ambiguous(green) > closed(gray) > normal(default color)
+        // precedence: current > remove > ambiguous > closed > normal (default color)
         let mut dir_name_color: Option<Color> = None;
         
         if curr_dir == self.curr_dir {
-            dir_name_color = Some(Color::Blue);
+            dir_name_color = Some(Color::CurrentDir);
         }
         if self.dir_to_remove.is_some() && self.dir_to_remove.as_ref().unwrap() == &curr_dir {
             remove = true;
-            if let None = dir_name_color {
-                dir_name_color = Some(Color::Red);
+            if dir_name_color.is_none() {
+                dir_name_color = Some(Color::Remove);
             }
         }
         if self.ambiguous_dirs.contains(&curr_dir) {
             ambiguous = true;
-            if let None = dir_name_color {
-                dir_name_color = Some(Color::Green);
+            if dir_name_color.is_none() {
+                dir_name_color = Some(Color::Ambiguous);
             }
         }
         if self.closed_dirs.contains(&curr_dir) {
             closed = true;
-            if let None = dir_name_color {
-                dir_name_color = Some(Color::DarkGray);
+            if dir_name_color.is_none() {
+                dir_name_color = Some(Color::Closed);
             }
         }
 
         // append all text related to the directory name
-        let mut directory_text = vec![];
-        directory_text.push(
-            if let Some(color) = dir_name_color { ColoredString::colored(curr_dir_name, color) }
-            else { ColoredString::normal(curr_dir_name) }
-        );
+        let mut directory_text = names::colored_name(&curr_dir.borrow().name, dir_name_color);
         if remove {
-            directory_text.push(ColoredString::colored(String::from(" x"), Color::Red));
+            directory_text.push(ColoredString::colored(String::from(" x"), Color::Remove));
         }
         if ambiguous {
-            directory_text.push(ColoredString::colored(format!(": {}", selected_dir_num), Color::Green));
+            directory_text.push(ColoredString::colored(format!(": {}", selected_dir_num), Color::Ambiguous));
             *selected_dir_num += 1;
         }
+        if curr_dir.borrow().error.is_some() {
+            directory_text.push(ColoredString::colored(String::from(" !"), Color::Error));
+        }
         if closed {
-            directory_text.push(ColoredString::colored(String::from(" +"), Color::DarkGray));
+            directory_text.push(ColoredString::colored(String::from(" +"), Color::Closed));
+        } else if !curr_dir.borrow().loaded {
+            // contents not read yet
+            directory_text.push(ColoredString::colored(String::from(" …"), Color::Closed));
         }
         contents.push(directory_text);
     
@@ -864,43 +1998,81 @@ impl<'a> StateManager<'a> {
     fn load_dir_contents(&self) -> Vec<Vec<ColoredString>> {
         let mut contents = vec![];
 
-        let files = &self.curr_dir.borrow().files;
-        let dirs = &self.curr_dir.borrow().directories;
+        let (files, dirs) = self.listed_entries();
 
-        if files.len() == 0 && dirs.len() == 0 {
+        if let Some(error) = &self.curr_dir.borrow().error {
+            contents.push(vec![ColoredString::colored(format!("This directory could not be read: {}", error), Color::Error)]);
+            return contents;
+        }
+
+        // the columns of every listed entry are laid out together, so that they line up
+        let borrowed_files: Vec<_> = files.iter().map(|file| file.borrow()).collect();
+        let borrowed_dirs: Vec<_> = dirs.iter().map(|dir| dir.borrow()).collect();
+        let entries: Vec<_> = borrowed_files.iter()
+            .map(|file| columns::Entry { name: &file.name, meta: file.meta.as_ref(), is_dir: false, is_link: file.is_link })
+            .chain(borrowed_dirs.iter().map(|dir| columns::Entry { name: &dir.name, meta: dir.meta.as_ref(), is_dir: true, is_link: false }))
+            .collect();
+        let columns = self.lay_out_columns(&entries);
+        let mut rows = columns.rows.iter().cloned();
+
+        if files.is_empty() && dirs.is_empty() && self.filter.is_none() {
             contents.push(vec![ColoredString::normal("This directory is empty".to_string())]);
         } else {
-            contents.push(vec![ColoredString::colored("Last Modified           Size  Name".to_string(), Color::Cyan)]);
-            contents.push(vec![ColoredString::colored("‾‾‾‾‾‾‾‾‾‾‾‾‾           ‾‾‾‾  ‾‾‾‾".to_string(), Color::Cyan)]);
+            // the heading ends with the order and filter of the listing
+            contents.push(vec![
+                ColoredString::colored(format!("   {}Name", columns.heading), Color::Header),
+                ColoredString::colored(format!("    {}", self.listing_indicator()), Color::Muted),
+            ]);
+            contents.push(vec![ColoredString::colored(format!("   {}‾‾‾‾", columns.underline), Color::Header)]);
+            if files.is_empty() && dirs.is_empty() {
+                contents.push(vec![ColoredString::normal("   Nothing matches the filter".to_string())]);
+            }
         }
 
-        if files.len() != 0 {
-            contents.push(vec![ColoredString::normal("- Files -".to_string())]);
-    
-            for file in files {
-                let last_mod = DateTime::<Utc>::from(file.borrow().meta.modified().unwrap());
+        // every entry starts with a gutter showing the highlighted row ('>') and marks ('*');
+        // the names of marked entries are highlighted as well
+        let gutter = |index: usize, path: &Path| {
+            let cursor = if self.contents_cursor == Some(index) { '>' } else { ' ' };
+            if self.marks.contains(path) {
+                (ColoredString::colored(format!("{}* ", cursor), Color::Mark), Some(Color::Mark))
+            } else {
+                (ColoredString::colored(format!("{}  ", cursor), Color::Header), None)
+            }
+        };
+
+        // entries which could not be read are marked; the reason is shown with the 'errors' command
+        let error_marker = |error: &Option<String>| if error.is_some() {
+            ColoredString::colored(String::from(" !"), Color::Error)
+        } else {
+            ColoredString::normal(String::new())
+        };
+
+        if !files.is_empty() {
+            contents.push(vec![ColoredString::normal("   - Files -".to_string())]);
     
-                let (pm, hour) = last_mod.hour12();
-                contents.push(vec![ColoredString::normal(format!("{:02}/{:02}/{:02} {:02}:{:02} {}  {:>7}  {}",
-                    last_mod.month(), last_mod.day(), last_mod.year(),       // last modified date
-                    hour, last_mod.minute(), if pm { "PM" } else { "AM" },   // last modified time
-                    file_size_to_str(file.borrow().meta.file_size()),                 // file size string
-                    file.borrow().name.to_str().unwrap()))]);                           // file name
+            for (i, file) in files.iter().enumerate() {
+                let file = file.borrow();
+                let (gutter, name_color) = gutter(i, &file.full_path);
+                let name_color = name_color.or_else(|| self.config.file_color(&file.name, file.is_link, file.meta.as_ref()));
+                let mut line = vec![gutter, ColoredString::normal(rows.next().unwrap())];
+                line.extend(names::colored_name(&file.name, name_color));                    // file name
+                line.push(error_marker(&file.error));
+                contents.push(line);
             }
             contents.push(vec![ColoredString::normal(String::new())]);
         }
 
-        if dirs.len() != 0 {
-            contents.push(vec![ColoredString::normal("- Directories -".to_string())]);
-    
-            for dir in dirs {
-                let last_mod = DateTime::<Utc>::from(dir.borrow().meta.modified().unwrap());
+        if !dirs.is_empty() {
+            contents.push(vec![ColoredString::normal("   - Directories -".to_string())]);
     
-                let (pm, hour) = last_mod.hour12();
-                contents.push(vec![ColoredString::normal(format!("{:02}/{:02}/{:02} {:02}:{:02} {}           {}",
-                    last_mod.month(), last_mod.day(), last_mod.year(),       // last modified date
-                    hour, last_mod.minute(), if pm { "PM" } else { "AM" },   // last modified time
-                    dir.borrow().name.to_str().unwrap()))]);                            // file name
+            for (i, dir) in dirs.iter().enumerate() {
+                let dir = dir.borrow();
+                let (gutter, name_color) = gutter(files.len() + i, &dir.full_path);
+                let name_color = name_color.or_else(|| self.config.file_color(&dir.name, false, dir.meta.as_ref()));
+                let mut line = vec![gutter, ColoredString::normal(rows.next().unwrap())];
+                line.extend(names::colored_name(&dir.name, name_color));                     // file name
+                line.push(error_marker(&dir.error));
+                contents.push(line);
             }
             
         }
@@ -908,6 +2080,72 @@ impl<'a> StateManager<'a> {
         contents
     }
 
+    // lists every entry in the loaded tree which could not be read, along with the reason
+    fn load_error_contents(&self) -> Vec<Vec<ColoredString>> {
+        fn collect(dir: &DirectoryRef, root: &Path, errors: &mut Vec<(PathBuf, String)>) {
+            let dir = dir.borrow();
+            let relative = |path: &Path| path.strip_prefix(root).unwrap_or(path).to_path_buf();
+            if let Some(error) = &dir.error {
+                errors.push((relative(&dir.full_path), error.clone()));
+            }
+            for file in &dir.files {
+                if let Some(error) = &file.borrow().error {
+                    errors.push((relative(&file.borrow().full_path), error.clone()));
+                }
+            }
+            for child in &dir.directories {
+                collect(child, root, errors);
+            }
+        }
+
+        let mut errors = vec![];
+        let root_path = self.root.borrow().full_path.clone();
+        collect(&self.root, &root_path, &mut errors);
+
+        if errors.is_empty() {
+            return vec![vec![ColoredString::normal("No unreadable entries".to_string())]];
+        }
+
+        let mut contents = vec![
+            vec![ColoredString::colored(format!("Unreadable entries ({})", errors.len()), Color::Header)],
+            vec![ColoredString::colored("‾‾‾‾‾‾‾‾‾‾‾‾‾‾‾‾‾‾".to_string(), Color::Header)],
+        ];
+        for (path, error) in errors {
+            let mut line = names::colored_name(path.as_os_str(), None);
+            line.push(ColoredString::normal(String::from(": ")));
+            line.push(ColoredString::colored(error, Color::Error));
+            contents.push(line);
+        }
+        contents
+    }
+
+    // lists the entries in the trash, along with where they were removed from
+    fn load_trash_contents(&self) -> Vec<Vec<ColoredString>> {
+        let entries = match self.trash.as_ref().unwrap().list() {
+            Ok(entries) => entries,
+            Err(error) => return vec![vec![ColoredString::colored(
+                format!("The trash could not be read: {}", scan::describe_error(&error)), Color::Error)]],
+        };
+
+        if entries.is_empty() {
+            return vec![vec![ColoredString::normal("The trash is empty".to_string())]];
+        }
+
+        let mut contents = vec![
+            vec![ColoredString::colored("Removed              Name  (Original Location)".to_string(), Color::Header)],
+            vec![ColoredString::colored("‾‾‾‾‾‾‾              ‾‾‾‾   ‾‾‾‾‾‾‾‾‾‾‾‾‾‾‾‾".to_string(), Color::Header)],
+        ];
+        for entry in entries {
+            let mut line = vec![ColoredString::normal(format!("{:19}  ", entry.deletion_date.replace('T', " ")))];
+            line.extend(names::colored_name(&entry.name, None));
+            line.push(ColoredString::colored(String::from("  ("), Color::Muted));
+            line.extend(names::colored_name(entry.original_path.as_os_str(), Some(Color::Muted)));
+            line.push(ColoredString::colored(String::from(")"), Color::Muted));
+            contents.push(line);
+        }
+        contents
+    }
+
     // reloads contents of (and redraws) the specified areas
     fn refresh_area(&mut self, tree: bool, contents: bool) -> io::Result<()> {
         // function to refresh an individual area (with new contents specified)
@@ -934,20 +2172,34 @@ impl<'a> StateManager<'a> {
         }
 
         if tree {
-            let contents = self.load_tree_contents(&mut 0, self.root.clone());
+            let mut contents = self.load_tree_contents(&mut 0, self.root.clone());
+            self.add_tree_cursor(&mut contents);
             refresh(self.term, &mut self.tree, contents)?;
         }
         if contents {
-            let contents = self.load_dir_contents();
+            let contents = match self.contents_view {
+                ContentsView::Directory => self.load_dir_contents(),
+                ContentsView::Errors => self.load_error_contents(),
+                ContentsView::Trash => self.load_trash_contents(),
+                ContentsView::Selection => self.load_selection_contents(),
+                ContentsView::RenamePreview => self.load_rename_preview(),
+                ContentsView::FindResults => self.load_find_results(),
+                ContentsView::Preview => self.load_preview_contents(),
+            };
             refresh(self.term, &mut self.dir_contents, contents)?;
+
+            let preview = self.preview_area.is_some().then(|| self.load_preview_contents());
+            if let (Some(area), Some(contents)) = (&mut self.preview_area, preview) {
+                refresh(self.term, area, contents)?;
+            }
         }
 
         Ok(())
     }
 
     // returns a list of possible directories which match the searched name/path
-    fn to_directory(&self, path: &str) -> Vec<DirectoryRef> {
-        let parts: Vec<&str> = path.split("/").collect();
+    fn to_directory(&self, path: &OsStr) -> Vec<DirectoryRef> {
+        let parts: Vec<&OsStr> = Path::new(path).components().map(|c| c.as_os_str()).collect();
         
         // narrows down the possible valid directories part by part of the path specified
         let mut possible = vec![self.root.clone()];
@@ -963,7 +2215,7 @@ impl<'a> StateManager<'a> {
     }
     
     // recursively goes through each directory in the tree and returns all matches
-    fn to_directory_helper(&self, path: &str, curr_dir: DirectoryRef, possible: &mut Vec<DirectoryRef>) {
+    fn to_directory_helper(&self, path: &OsStr, curr_dir: DirectoryRef, possible: &mut Vec<DirectoryRef>) {
         if curr_dir.borrow().name == path {
             possible.push(curr_dir.clone());
         }
@@ -979,21 +2231,22 @@ impl<'a> StateManager<'a> {
 }
 
 // draws borders around each area of the window
-fn draw_outline(term: &Term, selected_panel: CurrentArea) -> io::Result<()> {
+fn draw_outline(manager: &StateManager, selected_panel: CurrentArea) -> io::Result<()> {
+    let term = manager.term;
     let (height, width) = {
         let size = term.size();
         (size.0 as usize, size.1 as usize)
     };
 
-    let line_x = (width as f64 * 0.5) as usize;
+    let (line_x, preview_line_x) = manager.config.divider_positions(width, manager.preview_area.is_some());
 
     term.move_cursor_to(0, 0)?;
     term.clear_line()?;
-    term.write_line("DirMan")?;
+    term.write_str("DirMan")?;
 
     let print_with_color = |text: &str, colored_list: Vec<CurrentArea>| -> io::Result<()> {
         let colored = if colored_list.contains(&selected_panel) {
-            format!("{}", text.color(Color::Red))
+            paint(text, Color::Border)
         } else {
             text.to_string()
         };
@@ -1003,29 +2256,48 @@ fn draw_outline(term: &Term, selected_panel: CurrentArea) -> io::Result<()> {
     
     use CurrentArea::*;
 
-    for _ in 0..line_x {
-        print_with_color("━", vec![Tree])?;
-    }
-    print_with_color("┳", vec![Tree, Contents])?;
-    for _ in line_x+1..width {
-        print_with_color("━", vec![Contents])?;
+    // the panel which a column of the horizontal lines borders
+    let panel_at = |x: usize| if x < line_x {
+        Tree
+    } else if preview_line_x.is_some_and(|preview_line_x| x > preview_line_x) {
+        Preview
+    } else {
+        Contents
+    };
+
+    // the cursor is positioned explicitly on every line since the terminal is in raw mode
+    // and does not return to the first column on a newline
+    term.move_cursor_to(0, 1)?;
+    for x in 0..width {
+        if x == line_x {
+            print_with_color("┳", vec![Tree, Contents])?;
+        } else if Some(x) == preview_line_x {
+            print_with_color("┳", vec![Contents, Preview])?;
+        } else {
+            print_with_color("━", vec![panel_at(x)])?;
+        }
     }
-    term.move_cursor_down(1)?;
     
-    for _ in 0..height-4 {
-        term.move_cursor_right(line_x)?;
+    for y in 2..height-2 {
+        term.move_cursor_to(line_x, y)?;
         print_with_color("┃", vec![Tree, Contents])?;
-        term.move_cursor_down(1)?;
+        if let Some(preview_line_x) = preview_line_x {
+            term.move_cursor_to(preview_line_x, y)?;
+            print_with_color("┃", vec![Contents, Preview])?;
+        }
     }
 
-    for _ in 0..line_x {
-        print_with_color("━", vec![Tree, Command])?;
-    }
-    print_with_color("┻", vec![Tree, Contents, Command])?;
-    for _ in line_x+1..width {
-        print_with_color("━", vec![Contents, Command])?;
+    term.move_cursor_to(0, height - 2)?;
+    for x in 0..width {
+        if x == line_x {
+            print_with_color("┻", vec![Tree, Contents, Command])?;
+        } else if Some(x) == preview_line_x {
+            print_with_color("┻", vec![Contents, Preview, Command])?;
+        } else {
+            print_with_color("━", vec![panel_at(x), Command])?;
+        }
     }
-    term.move_cursor_down(1)?;
+    term.move_cursor_to(0, height - 1)?;
 
     Ok(())
 }
@@ -1046,43 +2318,185 @@ fn file_size_to_str(size: u64) -> String {
     }
 }
 
-fn load_dir(dir_path: PathBuf, parent: Option<DirectoryRef>) -> io::Result<DirectoryRef> {
-    // let mut files: Vec<FileRef> = vec![];
-    // let mut directories: Vec<DirectoryRef> = vec![];
-
-    let this_dir = Rc::new(RefCell::new(Directory::new(dir_path.clone(), vec![], vec![], parent)));
+// whether a name typed by the user can be used as the name of a single file or directory
+fn is_valid_name(name: &OsStr) -> bool {
+    let mut components = Path::new(name).components();
+    matches!((components.next(), components.next()), (Some(Component::Normal(_)), None))
+}
 
-    for entry in fs::read_dir(&dir_path)? {
-        let entry = entry?;
-        let entry_path = entry.path();
-        
-        if entry.file_type()?.is_dir() {
-            this_dir.borrow_mut().directories.push(load_dir(entry_path, Some(this_dir.clone()))?);
-        } else {
-            this_dir.borrow_mut().files.push(Rc::new(RefCell::new(File::new(entry.path()))));
-        }
+// performs a bound action in the selected panel
+fn perform_action(manager: &mut StateManager, action: Action, area: &mut CurrentArea, command: &mut String) -> io::Result<()> {
+    let term = manager.term;
+    let preview_pane = manager.preview_area.is_some();
+    match action {
+        Action::FocusCommand if *area != CurrentArea::Command => {
+            *area = CurrentArea::Command;
+            draw_outline(manager, CurrentArea::Command)?;
+            term.move_cursor_right(3 + command.chars().count())?;
+            term.show_cursor()?;
+        },
+        Action::FocusPanels if *area == CurrentArea::Command => {
+            *area = CurrentArea::Tree;
+            draw_outline(manager, CurrentArea::Tree)?;
+            term.hide_cursor()?;
+        },
+        Action::FocusLeft | Action::FocusRight => {
+            let next = match (*area, action) {
+                (CurrentArea::Contents, Action::FocusLeft) => CurrentArea::Tree,
+                (CurrentArea::Preview, Action::FocusLeft) => CurrentArea::Contents,
+                (CurrentArea::Tree, Action::FocusRight) => CurrentArea::Contents,
+                (CurrentArea::Contents, Action::FocusRight) if preview_pane => CurrentArea::Preview,
+                _ => return Ok(()),
+            };
+            *area = next;
+            draw_outline(manager, next)?;
+        },
+        Action::CursorUp | Action::CursorDown | Action::CursorFirst | Action::CursorLast => {
+            let movement = match action {
+                Action::CursorUp => CursorMove::Up,
+                Action::CursorDown => CursorMove::Down,
+                Action::CursorFirst => CursorMove::First,
+                _ => CursorMove::Last,
+            };
+            let result = match *area {
+                CurrentArea::Tree => manager.move_tree_cursor(movement),
+                CurrentArea::Contents => manager.move_contents_cursor(movement).and_then(|_| manager.preview_highlighted()),
+                // the preview pane has no cursor, so it scrolls by a line (or to an end)
+                CurrentArea::Preview => {
+                    let rows = match movement {
+                        CursorMove::Up => -1,
+                        CursorMove::Down => 1,
+                        CursorMove::First => isize::MIN,
+                        CursorMove::Last => isize::MAX,
+                    };
+                    manager.preview_area.as_mut().unwrap().scroll(term, rows, 0)
+                },
+                CurrentArea::Command => Ok(()),
+            };
+            manager.report_failure(result)?;
+        },
+        Action::ScrollUp | Action::ScrollDown | Action::ScrollLeft | Action::ScrollRight => {
+            let step = manager.config.layout.scroll_step as isize;
+            let panel = match *area {
+                CurrentArea::Tree => &mut manager.tree,
+                CurrentArea::Contents => &mut manager.dir_contents,
+                CurrentArea::Preview => manager.preview_area.as_mut().unwrap(),
+                CurrentArea::Command => return Ok(()),
+            };
+            match action {
+                Action::ScrollUp => panel.scroll(term, -step, 0)?,
+                Action::ScrollDown => panel.scroll(term, step, 0)?,
+                Action::ScrollLeft => panel.scroll(term, 0, -step)?,
+                _ => panel.scroll(term, 0, step)?,
+            }
+        },
+        Action::Open => {
+            let result = manager.open_highlighted(*area);
+            manager.report_failure(result)?;
+        },
+        Action::Remove | Action::Rename | Action::Copy | Action::Move => {
+            let key = match action {
+                Action::Remove => 'x',
+                Action::Rename => 'r',
+                Action::Copy => 'c',
+                _ => 'm',
+            };
+            // actions which need more input are finished on the command line
+            match manager.act_on_highlighted(*area, key) {
+                Ok(Some(text)) => {
+                    *command = text;
+                    *area = CurrentArea::Command;
+                    draw_outline(manager, CurrentArea::Command)?;
+                    term.clear_line()?;
+                    term.write_str(" > ")?;
+                    term.write_str(command)?;
+                    term.show_cursor()?;
+                },
+                Ok(None) => {},
+                Err(error) => manager.report_failure(Err(error))?,
+            }
+        },
+        Action::ToggleMark if *area == CurrentArea::Contents => {
+            manager.toggle_mark_at_cursor()?;
+            manager.preview_highlighted()?;
+        },
+        _ => {},
     }
+    Ok(())
+}
+
+// reports an invalid invocation and exits
+fn usage_error(message: &str) -> ! {
+    eprintln!("dirman: {}", message);
+    eprintln!("Try 'dirman --help' for more information.");
+    process::exit(2);
+}
+
+// restores the terminal to its normal mode when the program exits (including on error)
+struct RawModeGuard;
 
-    Ok(this_dir)
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
 }
 
 fn main() -> io::Result<()> {
     // parse command line arguments and extract directory
-    let path = env::current_dir()?;
+    let args = match args::parse(env::args_os().skip(1)) {
+        Ok(args::Invocation::Run(args)) => args,
+        Ok(args::Invocation::Help) => {
+            println!("{}", args::USAGE);
+            return Ok(());
+        },
+        Ok(args::Invocation::Version) => {
+            println!("dirman {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        },
+        Ok(args::Invocation::ShellInit(shell)) => {
+            println!("{}", shell_init::function(&shell).unwrap());
+            return Ok(());
+        },
+        Err(message) => usage_error(&message),
+    };
+
+    let path = match &args.path {
+        Some(path) => path.clone(),
+        None => env::current_dir()?,
+    };
     if !path.is_dir() {
-        println!("Input directory does not exist");
-        return Ok(());
+        usage_error(&format!("'{}' is not a directory", path.display()));
     }
+    // the root needs a proper name to be displayed, so resolve paths such as '.' or '..'
+    let path = fs::canonicalize(&path)
+        .unwrap_or_else(|e| usage_error(&format!("cannot open '{}': {}", path.display(), e)));
 
-    // construct directory tree
-    let root = load_dir(path, None)?;
+    if let Some(config) = &args.config {
+        if !config.is_file() {
+            usage_error(&format!("config file '{}' does not exist", config.display()));
+        }
+    }
+    let config = Config::load(args.config.as_deref()).unwrap_or_else(|message| {
+        eprintln!("dirman: {}", message);
+        process::exit(1);
+    });
+
+    config.apply_theme(args.no_color);
+
+    // construct directory tree; only the root is read here and the rest is read as needed
+    let root = Rc::new(RefCell::new(Directory::new(path, None)));
 
     let term = Term::stdout();
 
+    // key events are read directly from the terminal; raw mode stops the terminal from
+    // echoing input and buffering it until enter is pressed
+    terminal::enable_raw_mode().map_err(io::Error::other)?;
+    let _raw_mode = RawModeGuard;
+
     // find dimensions for screen areas
     let size = Vector2 { x: term.size().1 as usize, y: term.size().0 as usize };
-    if size.x < 75 || size.y < 10 {
-        println!("Terminal window too small; terminal must be at least 75 units wide and 15 tall");
+    if !config.window_fits(size.x, size.y) {
+        println!("Terminal window too small; terminal must be at least {} units wide and {} tall", config::MIN_WINDOW_WIDTH, config::MIN_WINDOW_HEIGHT);
         return Ok(());
     }
 
@@ -1090,28 +2504,41 @@ fn main() -> io::Result<()> {
         term.write_line("")?;
     }
 
-    draw_outline(&term, CurrentArea::Command)?;
+    let mut manager = StateManager::init(&term, root.clone(), args.max_depth, args.show_hidden, args.no_color, config)?;
+
+    draw_outline(&manager, CurrentArea::Command)?;
     term.clear_line()?;
     term.write_str(" > ")?;
 
-    let mut manager = StateManager::init(&term, root.clone())?;
-
     term.move_cursor_to(3, size.y - 1)?;
 
     let mut curr_area_tag = CurrentArea::Command;
     
     let mut command = String::new();
+    // keys pressed so far of a binding of several keys
+    let mut pending_keys = vec![];
     loop {
-        match event::read().unwrap() {
-            Event::Resize(width, height) => {
-                let (width, height) = (width as usize, height as usize);
+        // while there is no input, add directories read by the background scan to the tree and
+        // show the preview once it has been highlighted
+        if !event::poll(Duration::from_millis(50)).map_err(io::Error::other)? {
+            let scanned = manager.apply_scan_results()?;
+            let highlighted = manager.apply_highlighting()?;
+            // the results of a search renumber the '#N' entries, so they wait for a command being
+            // typed to be entered
+            let searched = command.is_empty() && manager.apply_search_results()?;
+            if (scanned || highlighted || searched) && curr_area_tag == CurrentArea::Command {
+                // drawing moves the cursor so put it back at the end of the command
+                term.move_cursor_to(3 + command.chars().count(), manager.term.size().0 as usize - 1)?;
+            }
+            continue;
+        }
 
-                let line_x = width / 2;
-                manager.tree.size = Vector2 { x: line_x, y: height - 4 };
-                manager.dir_contents.size = Vector2 { x: width - line_x - 1, y: height - 4 };
-                manager.dir_contents.screen_offset = Vector2 { x: line_x + 1, y: 2 };
+        match event::read().unwrap() {
+            Event::Resize(_, height) => {
+                let height = height as usize;
+                manager.layout();
 
-                draw_outline(&term, curr_area_tag)?;
+                draw_outline(&manager, curr_area_tag)?;
                 term.clear_line()?;
                 term.write_str(" > ")?;
                 term.write_str(&command)?;
@@ -1119,93 +2546,37 @@ fn main() -> io::Result<()> {
                 term.move_cursor_to(3 + command.chars().count(), height - 1)?;
             },
             Event::Key(key_event) => {
-                let key = key_event.code;
                 use crossterm::event::KeyCode::*;
-                match key {
-                    Up => {
-                        if let CurrentArea::Command = curr_area_tag {
-                            curr_area_tag = CurrentArea::Tree;
-                            draw_outline(&term, CurrentArea::Tree)?;
-                            term.hide_cursor()?;
-                        }
-                    },
-                    Right => {
-                        if let CurrentArea::Tree = curr_area_tag {
-                            curr_area_tag = CurrentArea::Contents;
-                            draw_outline(&term, CurrentArea::Contents)?;
-                        }
-                    },
-                    Down | Esc => {
-                        if curr_area_tag != CurrentArea::Command {
-                            curr_area_tag = CurrentArea::Command;
-                            draw_outline(&term, CurrentArea::Command)?;
-                            term.move_cursor_right(3 + command.chars().count())?;
-                            term.show_cursor()?;
-                        }
-                    },
-                    Left => {
-                        if let CurrentArea::Contents = curr_area_tag {
-                            curr_area_tag = CurrentArea::Tree;
-                            draw_outline(&term, CurrentArea::Tree)?;
-                        }
-                    },
-                    Char(c) => {
-                        if let CurrentArea::Command = curr_area_tag {
+                match manager.config.keymap.feed(curr_area_tag, &mut pending_keys, key_event) {
+                    Feed::Action(action) => perform_action(&mut manager, action, &mut curr_area_tag, &mut command)?,
+                    Feed::Pending => {},
+                    // other keys edit the command line
+                    Feed::Unbound(key) if curr_area_tag == CurrentArea::Command => match key.code {
+                        Char(c) => {
                             command.push(c);
                             term.write_str(&c.to_string())?;
-                        } else {
-                            let curr_area = match curr_area_tag {
-                                CurrentArea::Tree => &mut manager.tree,
-                                CurrentArea::Contents => &mut manager.dir_contents,
-                                _ => &mut manager.tree,
-                            };
-
-                            match c {
-                                // WASD control scrolling if a scrollable area is selected
-                                'w' | 'W' => if curr_area.curr_pos.y != 0 {
-                                    curr_area.curr_pos.y -= min(curr_area.curr_pos.y, 5);
-                                    curr_area.draw(&term)?;
-                                },
-                                'a' | 'A' => if curr_area.curr_pos.x != 0 {
-                                    curr_area.curr_pos.x -= min(curr_area.curr_pos.x, 5);
-                                    curr_area.draw(&term)?;
-                                },
-                                's' | 'S' => if curr_area.contents_size().y + curr_area.curr_pos.y < curr_area.contents.len() {
-                                    curr_area.curr_pos.y += min(
-                                        curr_area.contents.len() - curr_area.contents_size().y - curr_area.curr_pos.y,
-                                        5);
-                                    curr_area.draw(&term)?;
-                                },
-                                'd' | 'D' => if curr_area.contents_size().x + curr_area.curr_pos.x < curr_area.longest_line_len {
-                                    curr_area.curr_pos.x += min(
-                                        curr_area.longest_line_len - curr_area.contents_size().x - curr_area.curr_pos.x,
-                                        5);
-                                    curr_area.draw(&term)?;
-                                },
-                                _ => {},
+                        },
+                        Enter => {
+                            if command == "q" {
+                                break;
                             }
-                        }
-                    },
-                    Enter => {
-                        if command == "q" {
-                            break;
-                        }
-                        
-                        manager.process_command(&command)?;
 
-                        let num_chars = command.chars().count();
-                        term.move_cursor_to(3 + num_chars, manager.term.size().0 as usize - 1)?;
-                        term.clear_chars(num_chars)?;
-                        
-                        command.clear();
-                    }
-                    Backspace => {
-                        if command.chars().count() != 0 {
+                            let result = manager.process_command(&command);
+                            manager.report_failure(result)?;
+
+                            let num_chars = command.chars().count();
+                            term.move_cursor_to(3 + num_chars, manager.term.size().0 as usize - 1)?;
+                            term.clear_chars(num_chars)?;
+
+                            command.clear();
+                        },
+                        Backspace if !command.is_empty() => {
                             command.pop();
                             term.clear_chars(1)?;
-                        }
-                    }
-                    _ => {},
+                        },
+                        _ => {},
+                    },
+                    Feed::Unbound(_) => {},
                 }
             }
             _ => {},
@@ -1218,5 +2589,13 @@ fn main() -> io::Result<()> {
     }
     term.clear_screen()?;
 
+    // tells the shell function from --shell-init which directory to change to
+    if let Some(cwd_file) = &args.cwd_file {
+        let curr_dir = manager.curr_dir.borrow().full_path.clone();
+        if let Err(error) = fs::write(cwd_file, platform::path_bytes(&curr_dir)) {
+            eprintln!("dirman: cannot write '{}': {}", cwd_file.display(), error);
+        }
+    }
+
     Ok(())
 }
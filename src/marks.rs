@@ -0,0 +1,506 @@
+// marking of several entries in the current directory, and the commands which apply to several
+// entries at once (move, copy, remove and rename); these apply either to the entries matched by a
+// pattern argument (see the pattern module) or to the marked entries
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::{fsops, names, scan};
+use crate::theme::Color;
+use crate::{ColoredString, ContentsView, Directory, DirQuery, StateManager, Vector2};
+use crate::journal::Change;
+use crate::navigation::CursorMove;
+use crate::pattern::{self, Component, Pattern};
+
+// the outcome of applying a command to every marked entry
+#[derive(Default)]
+pub struct Summary {
+    pub done: usize,
+    // names of the entries which failed, with the reason
+    pub failures: Vec<(OsString, String)>,
+}
+
+impl<'a> StateManager<'a> {
+    // names and paths of the entries in the current directory, in the order they are shown
+    pub fn current_entries(&self) -> Vec<(OsString, PathBuf)> {
+        let (files, dirs) = self.listed_entries();
+        let files = files.iter().map(|f| (f.borrow().name.clone(), f.borrow().full_path.clone()));
+        let dirs = dirs.iter().map(|d| (d.borrow().name.clone(), d.borrow().full_path.clone()));
+        files.chain(dirs).collect()
+    }
+
+    // the marked entries which are still in the current directory, in the order they are shown
+    fn marked_entries(&self) -> Vec<(OsString, PathBuf)> {
+        self.current_entries().into_iter().filter(|(_, path)| self.marks.contains(path)).collect()
+    }
+
+    pub fn has_marks(&self) -> bool {
+        !self.marked_entries().is_empty()
+    }
+
+    // the entries a batch command applies to: those matched by a pattern argument if there was
+    // one, otherwise the marked entries
+    fn batch_entries(&self) -> Vec<(OsString, PathBuf)> {
+        match &self.selection {
+            Some(paths) => paths.iter().map(|path| (path.file_name().unwrap().to_os_string(), path.clone())).collect(),
+            None => self.marked_entries(),
+        }
+    }
+
+    // finds the entries matching a pattern, starting from the current directory; directories
+    // which have not been read yet are read on the way
+    pub fn expand_pattern(&mut self, pattern: &Pattern) -> io::Result<Vec<PathBuf>> {
+        let mut dirs = vec![self.curr_dir.clone()];
+        let mut matches = vec![];
+        for (i, component) in pattern.components.iter().enumerate() {
+            let last = i == pattern.components.len() - 1;
+            match component {
+                Component::AnyDepth => {
+                    // every directory below, including the ones themselves
+                    let mut all = vec![];
+                    while let Some(dir) = dirs.pop() {
+                        self.ensure_loaded(&dir)?;
+                        dirs.extend(dir.borrow().directories.iter().cloned());
+                        all.push(dir);
+                    }
+                    dirs = all;
+                    // a trailing '**' matches everything inside
+                    if last {
+                        for dir in &dirs {
+                            let dir = dir.borrow();
+                            matches.extend(dir.files.iter().map(|f| f.borrow().full_path.clone()));
+                            matches.extend(dir.directories.iter().map(|d| d.borrow().full_path.clone()));
+                        }
+                    }
+                },
+                Component::Name(matcher) => {
+                    let mut next = vec![];
+                    for dir in &dirs {
+                        self.ensure_loaded(dir)?;
+                        let dir = dir.borrow();
+                        if last {
+                            matches.extend(dir.files.iter()
+                                .filter(|f| matcher.is_match(&f.borrow().name))
+                                .map(|f| f.borrow().full_path.clone()));
+                        }
+                        for child in dir.directories.iter().filter(|d| matcher.is_match(&d.borrow().name)) {
+                            if last {
+                                matches.push(child.borrow().full_path.clone());
+                            } else {
+                                next.push(child.clone());
+                            }
+                        }
+                    }
+                    dirs = next;
+                },
+            }
+        }
+
+        // entries inside of a matched directory are already covered by it (and '**' can match
+        // the same entry more than once); paths sort by component, so everything inside of a
+        // directory comes right after it
+        matches.sort();
+        let mut kept: Vec<PathBuf> = vec![];
+        for path in matches {
+            if !kept.last().is_some_and(|last| path.starts_with(last)) {
+                kept.push(path);
+            }
+        }
+        Ok(kept)
+    }
+
+    // if an argument is a pattern, selects the entries it matches for the next batch command
+    // and returns true; problems with the pattern are reported here, leaving nothing selected
+    pub fn select_pattern(&mut self, arg: &OsStr) -> io::Result<bool> {
+        let pattern = match pattern::parse(arg) {
+            Ok(Some(pattern)) => pattern,
+            Ok(None) => return Ok(false),
+            Err(message) => {
+                self.print_error(&message)?;
+                return Ok(true);
+            },
+        };
+
+        let matched = self.expand_pattern(&pattern)?;
+        if matched.is_empty() {
+            self.print_error(&format!("Nothing in the current directory matches '{}'", names::display_name(arg)))?;
+        } else {
+            self.selection = Some(matched);
+        }
+        Ok(true)
+    }
+
+    // lists the selected entries in the contents panel and asks whether to go ahead
+    pub fn preview_selection(&mut self, question: &str) -> io::Result<()> {
+        self.contents_view = ContentsView::Selection;
+        self.dir_contents.curr_pos = Vector2 { x: 0, y: 0 };
+        self.refresh_area(false, true)?;
+        self.print_error(question)
+    }
+
+    // forgets the entries matched by a pattern if a batch command did not get to use them
+    pub fn clear_selection(&mut self) -> io::Result<()> {
+        if self.selection.take().is_some() && self.contents_view == ContentsView::Selection {
+            self.contents_view = ContentsView::Directory;
+            self.refresh_area(false, true)?;
+        }
+        Ok(())
+    }
+
+    // lists the entries matched by a pattern, relative to the current directory
+    pub fn load_selection_contents(&self) -> Vec<Vec<ColoredString>> {
+        let paths = self.selection.as_deref().unwrap_or_default();
+        let curr_path = self.curr_dir.borrow().full_path.clone();
+        let header = format!("Matched entries ({})", paths.len());
+        let underline = "‾".repeat(header.len());
+        let mut contents = vec![
+            vec![ColoredString::colored(header, Color::Header)],
+            vec![ColoredString::colored(underline, Color::Header)],
+        ];
+        for path in paths {
+            let relative = path.strip_prefix(&curr_path).unwrap_or(path);
+            let color = if self.find_loaded_dir(path).is_some() { Some(Color::Directory) } else { None };
+            contents.push(names::colored_name(relative.as_os_str(), color));
+        }
+        contents
+    }
+
+    // whether an entry of the tree is a directory, and the reason it (or anything inside of it)
+    // could not be read, if any
+    fn entry_info(&self, path: &Path) -> (bool, Option<String>) {
+        if let Some(dir) = self.find_loaded_dir(path) {
+            return (true, Directory::find_error(&dir));
+        }
+        let error = path.parent()
+            .and_then(|parent| self.find_loaded_dir(parent))
+            .and_then(|parent| parent.borrow().files.iter()
+                .find(|f| f.borrow().full_path == path)
+                .and_then(|f| f.borrow().error.clone()));
+        (false, error)
+    }
+
+    // marks (or unmarks) the entries of the current directory which match a name, a pattern
+    // or 'all'
+    pub fn set_marks(&mut self, pattern: &OsStr, mark: bool) -> io::Result<()> {
+        let entries = self.current_entries();
+        let matched: Vec<PathBuf> = if pattern == "all" {
+            entries.into_iter().map(|(_, path)| path).collect()
+        } else if let Some((_, path)) = entries.iter().find(|(name, _)| name == pattern) {
+            vec![path.clone()]
+        } else {
+            match pattern::parse(pattern) {
+                // marks only apply to entries of the current directory
+                Ok(Some(pattern)) => {
+                    let curr_path = self.curr_dir.borrow().full_path.clone();
+                    self.expand_pattern(&pattern)?.into_iter()
+                        .filter(|path| path.parent() == Some(curr_path.as_path()))
+                        .collect()
+                },
+                Ok(None) => vec![],
+                Err(message) => {
+                    self.print_error(&message)?;
+                    return Ok(());
+                },
+            }
+        };
+
+        if matched.is_empty() {
+            self.print_error(&format!("Nothing in the current directory matches '{}'", names::display_name(pattern)))?;
+            return Ok(());
+        }
+
+        for path in matched {
+            if mark {
+                self.marks.insert(path);
+            } else {
+                self.marks.remove(&path);
+            }
+        }
+        // marks are shown in the list of the current directory
+        self.contents_view = ContentsView::Directory;
+        self.refresh_area(false, true)?;
+        Ok(())
+    }
+
+    // moves the highlighted row of the contents panel up or down
+    pub fn move_contents_cursor(&mut self, movement: CursorMove) -> io::Result<()> {
+        let count = match self.contents_view {
+            ContentsView::Directory => self.current_entries().len(),
+            ContentsView::FindResults => self.find_results.as_ref().map_or(0, |results| results.entries.len()),
+            _ => 0,
+        };
+        if count == 0 {
+            return Ok(());
+        }
+
+        let cursor = movement.apply(self.contents_cursor, 0, count);
+        self.set_contents_cursor(cursor)
+    }
+
+    // highlights a row of the contents panel, scrolling so that it is visible
+    pub fn set_contents_cursor(&mut self, cursor: usize) -> io::Result<()> {
+        self.contents_cursor = Some(cursor);
+        let row = self.contents_row(cursor);
+        let area = &mut self.dir_contents;
+        if row < area.curr_pos.y {
+            area.curr_pos.y = row;
+        } else if row >= area.curr_pos.y + area.contents_size().y {
+            area.curr_pos.y = row + 1 - area.contents_size().y;
+        }
+        self.refresh_area(false, true)
+    }
+
+    // marks the highlighted entry, or unmarks it if it is already marked
+    pub fn toggle_mark_at_cursor(&mut self) -> io::Result<()> {
+        if self.contents_view != ContentsView::Directory {
+            return Ok(());
+        }
+        let entries = self.current_entries();
+        if let Some((_, path)) = self.contents_cursor.and_then(|cursor| entries.get(cursor)) {
+            if !self.marks.remove(path) {
+                self.marks.insert(path.clone());
+            }
+            self.refresh_area(false, true)?;
+        }
+        Ok(())
+    }
+
+    // row of the contents panel that an entry of the current directory is shown on
+    fn contents_row(&self, index: usize) -> usize {
+        // search results follow a header of three lines; results of 'grep' are followed by
+        // their matched lines
+        if let (ContentsView::FindResults, Some(results)) = (self.contents_view, &self.find_results) {
+            return 3 + index + results.entries[..index].iter().map(|result| result.lines.len()).sum::<usize>();
+        }
+        // a header of two lines and '- Files -'; the files are followed by an empty line
+        // and '- Directories -'
+        let files = self.listed_entries().0.len();
+        if index < files || files == 0 {
+            3 + index
+        } else {
+            5 + index
+        }
+    }
+
+    // moves the entries of a batch into a different directory
+    pub fn move_batch_to_dir(&mut self, dir: DirQuery, other_arg: &OsStr) -> io::Result<()> {
+        if let Some(dest) = self.get_dir(Self::move_batch_to_dir, dir, other_arg)? {
+            // the moved entries are added to the destination's contents, so they need to be known
+            self.ensure_loaded(&dest)?;
+            let dest_path = dest.borrow().full_path.clone();
+
+            let mut summary = Summary::default();
+            let mut changes = vec![];
+            for (name, path) in self.batch_entries() {
+                let result = if path.parent() == Some(dest_path.as_path()) {
+                    Err(String::from("already in the destination directory"))
+                } else if dest_path.starts_with(&path) {
+                    Err(String::from("cannot move a directory into itself"))
+                } else {
+                    let new_path = Self::get_copy_path(dest_path.join(&name));
+                    fsops::move_path(&path, &new_path)
+                        .map(|()| {
+                            self.relocate_in_tree(&path, &new_path);
+                            changes.push(Change::Moved { from: path, to: new_path });
+                        })
+                        .map_err(|e| scan::describe_error(&e))
+                };
+                summary.add(name, result);
+            }
+
+            self.finish_batch(summary, changes, "move", "Moved", &format!(" to '{}'", names::display_name(&dest.borrow().name)))?;
+        }
+        Ok(())
+    }
+
+    // copies the entries of a batch into a different directory
+    pub fn copy_batch_to_dir(&mut self, dir: DirQuery, other_arg: &OsStr) -> io::Result<()> {
+        if let Some(dest) = self.get_dir(Self::copy_batch_to_dir, dir, other_arg)? {
+            let dest_path = dest.borrow().full_path.clone();
+
+            let mut summary = Summary::default();
+            let mut changes = vec![];
+            for (name, path) in self.batch_entries() {
+                // the whole subtree (or the file's contents) needs to be read to copy it
+                let (is_dir, error) = self.entry_info(&path);
+
+                let result = if let Some(error) = error {
+                    Err(error)
+                } else if is_dir && dest_path.starts_with(&path) {
+                    Err(String::from("cannot copy a directory into itself"))
+                } else {
+                    let new_path = Self::get_copy_path(dest_path.join(&name));
+                    self.journal.created(new_path.clone())
+                        .and_then(|change| {
+                            if is_dir {
+                                fsops::copy_dir_all(&path, &new_path)?;
+                            } else {
+                                fs::copy(&path, &new_path)?;
+                            }
+                            Ok(change)
+                        })
+                        .map(|change| {
+                            self.put_in_tree(&new_path, None);
+                            changes.push(change);
+                        })
+                        .map_err(|e| scan::describe_error(&e))
+                };
+                summary.add(name, result);
+            }
+
+            self.finish_batch(summary, changes, "copy", "Copied", &format!(" to '{}'", names::display_name(&dest.borrow().name)))?;
+        }
+        Ok(())
+    }
+
+    // removes the entries of a batch; they are moved to the trash unless permanent is set
+    pub fn remove_batch(&mut self, permanent: bool) -> io::Result<()> {
+        if !permanent && self.trash.is_none() {
+            self.print_error("The trash is not available; use 'remove --permanent' to remove permanently")?;
+            return Ok(());
+        }
+
+        let mut summary = Summary::default();
+        let mut changes = vec![];
+        for (name, path) in self.batch_entries() {
+            let result = if permanent {
+                // a directory with unreadable contents could only be partially removed
+                match self.entry_info(&path) {
+                    (true, Some(error)) => Err(error),
+                    (true, None) => fs::remove_dir_all(&path).map_err(|e| scan::describe_error(&e)),
+                    (false, _) => fs::remove_file(&path).map_err(|e| scan::describe_error(&e)),
+                }
+            } else {
+                self.trash.as_ref().unwrap().put(path.clone())
+                    .map(|change| changes.push(change))
+                    .map_err(|e| scan::describe_error(&e))
+            };
+            if result.is_ok() {
+                self.take_from_tree(&path);
+            }
+            summary.add(name, result);
+        }
+
+        self.forget_detached_dirs();
+        self.finish_batch(summary, changes, "remove", "Removed", "")
+    }
+
+    // renames every marked entry after a template, in which '{n}' is replaced by the entry's
+    // number (starting from 1), '{name}' by its name without the extension and '{ext}' by its
+    // extension (including the dot, if it has one)
+    pub fn rename_marked(&mut self, template: &OsStr) -> io::Result<()> {
+        let template = match template.to_str() {
+            Some(template) => template,
+            None => {
+                self.print_error("The name template must be valid unicode")?;
+                return Ok(());
+            },
+        };
+        let entries = self.marked_entries();
+        if entries.len() > 1 && !template.contains("{n}") {
+            self.print_error("The name template must contain '{n}' so that the new names are different")?;
+            return Ok(());
+        }
+
+        let mut summary = Summary::default();
+        let mut changes = vec![];
+        for (i, (name, path)) in entries.into_iter().enumerate() {
+            let new_name = expand_template(template, i + 1, &name);
+            let new_path = path.with_file_name(&new_name);
+            let result = if !crate::is_valid_name(&new_name) {
+                Err(String::from("the new name must not be empty or contain path separators"))
+            } else if new_name == name {
+                Ok(())
+            } else if new_path.symlink_metadata().is_ok() {
+                Err(format!("'{}' already exists", names::display_name(&new_name)))
+            } else {
+                fs::rename(&path, &new_path)
+                    .map(|()| {
+                        self.relocate_in_tree(&path, &new_path);
+                        changes.push(Change::Moved { from: path, to: new_path });
+                    })
+                    .map_err(|e| scan::describe_error(&e))
+            };
+            summary.add(name, result);
+        }
+
+        self.finish_batch(summary, changes, "rename", "Renamed", "")
+    }
+
+    // records a batch in the journal, clears the marks (or the pattern's matches) and reports the outcome
+    pub fn finish_batch(&mut self, summary: Summary, changes: Vec<Change>, verb: &str, past: &str, suffix: &str) -> io::Result<()> {
+        if !changes.is_empty() {
+            self.journal.record(format!("{} {}{}", verb, entry_count(summary.done), suffix), changes);
+        }
+
+        self.marks.clear();
+        self.selection = None;
+        if self.contents_view == ContentsView::Selection {
+            self.contents_view = ContentsView::Directory;
+        }
+        self.contents_cursor = None;
+        self.dir_contents.curr_pos = Vector2 { x: 0, y: 0 };
+        self.refresh_area(true, true)?;
+
+        match summary.failures.first() {
+            None => self.print_message(&format!("{} {}{}", past, entry_count(summary.done), suffix)),
+            Some((name, reason)) => {
+                let total = summary.done + summary.failures.len();
+                let mut message = format!("{} {} of {}; could not {} '{}': {}",
+                    past, summary.done, entry_count(total), verb, names::display_name(name), reason);
+                if summary.failures.len() > 1 {
+                    message.push_str(&format!(" (and {} more)", summary.failures.len() - 1));
+                }
+                self.print_error(&message)
+            },
+        }
+    }
+}
+
+impl Summary {
+    fn add(&mut self, name: OsString, result: Result<(), String>) {
+        match result {
+            Ok(()) => self.done += 1,
+            Err(reason) => self.failures.push((name, reason)),
+        }
+    }
+}
+
+pub fn entry_count(count: usize) -> String {
+    if count == 1 {
+        String::from("1 entry")
+    } else {
+        format!("{} entries", count)
+    }
+}
+
+fn expand_template(template: &str, number: usize, name: &OsStr) -> OsString {
+    let path = Path::new(name);
+    let stem = path.file_stem().unwrap_or(name);
+    let ext = path.extension();
+
+    let mut expanded = OsString::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        expanded.push(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("{n}") {
+            expanded.push(number.to_string());
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("{name}") {
+            expanded.push(stem);
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("{ext}") {
+            if let Some(ext) = ext {
+                expanded.push(".");
+                expanded.push(ext);
+            }
+            rest = after;
+        } else {
+            expanded.push("{");
+            rest = &rest[1..];
+        }
+    }
+    expanded.push(rest);
+    expanded
+}
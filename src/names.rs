@@ -0,0 +1,164 @@
+// conversion between file names (which may not be valid unicode) and the text shown in the
+// interface; names that cannot be shown as-is are displayed with escape sequences, and the
+// same escape sequences can be typed in commands to refer to them (see the command module)
+//
+// on unix invalid bytes and control characters are written as '\xNN'; on windows unpaired
+// surrogates are written as '\u{NNNN}'; in an escaped name a backslash is written as '\\'
+use std::ffi::OsStr;
+use crate::theme::Color;
+use crate::ColoredString;
+
+// NameBuilder builds a name out of text and raw units (bytes on unix, utf-16 code units on
+// windows); parse_escape parses an escape sequence at the start of some text into a raw unit
+pub use imp::{parse_escape, NameBuilder};
+
+// color used to set escape sequences apart from the rest of a name
+const ESCAPE_COLOR: Color = Color::Escape;
+
+// a piece of a displayed name; escaped pieces are not part of the name as-is
+enum Piece {
+    Text(String),
+    Escaped(String),
+}
+
+// returns the text to show for a name; names which are valid unicode without control
+// characters are returned unchanged
+pub fn display_name(name: &OsStr) -> String {
+    pieces(name).into_iter()
+        .map(|piece| match piece {
+            Piece::Text(s) | Piece::Escaped(s) => s,
+        })
+        .collect()
+}
+
+// same as display_name, but with escape sequences colored so that they stand out; the rest
+// of the name is given the specified color
+pub fn colored_name(name: &OsStr, color: Option<Color>) -> Vec<ColoredString> {
+    pieces(name).into_iter()
+        .map(|piece| match piece {
+            Piece::Text(s) => ColoredString { string: s, color },
+            Piece::Escaped(s) => ColoredString::colored(s, ESCAPE_COLOR),
+        })
+        .collect()
+}
+
+fn pieces(name: &OsStr) -> Vec<Piece> {
+    // the common case: nothing needs to be escaped
+    if let Some(s) = name.to_str() {
+        if !s.chars().any(char::is_control) {
+            return vec![Piece::Text(s.to_string())];
+        }
+    }
+
+    let mut pieces = vec![];
+    let mut text = String::new();
+    imp::for_each_unit(name, |unit| match unit {
+        Ok('\\') => text.push_str("\\\\"),
+        // control characters would disturb the terminal if they were printed
+        Ok(c) if !c.is_control() => text.push(c),
+        Ok(c) => {
+            pieces.push(Piece::Text(std::mem::take(&mut text)));
+            pieces.push(Piece::Escaped(imp::escape_char(c)));
+        },
+        Err(raw) => {
+            pieces.push(Piece::Text(std::mem::take(&mut text)));
+            pieces.push(Piece::Escaped(imp::escape_raw(raw)));
+        },
+    });
+    pieces.push(Piece::Text(text));
+    pieces.retain(|piece| !matches!(piece, Piece::Text(s) if s.is_empty()));
+    pieces
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::ffi::{OsStr, OsString};
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+    // calls f for every character of the name, or with the raw byte for invalid bytes
+    pub fn for_each_unit<F: FnMut(Result<char, u8>)>(name: &OsStr, mut f: F) {
+        for chunk in name.as_bytes().utf8_chunks() {
+            chunk.valid().chars().for_each(|c| f(Ok(c)));
+            chunk.invalid().iter().for_each(|b| f(Err(*b)));
+        }
+    }
+
+    pub fn escape_char(c: char) -> String {
+        let mut buf = [0; 4];
+        c.encode_utf8(&mut buf).bytes().map(escape_raw).collect()
+    }
+
+    pub fn escape_raw(byte: u8) -> String {
+        format!("\\x{:02X}", byte)
+    }
+
+    // parses a '\xNN' sequence at the start of the text; returns the byte and length of the sequence
+    pub fn parse_escape(text: &str) -> Option<(u8, usize)> {
+        let hex = text.strip_prefix("\\x")?.get(..2)?;
+        u8::from_str_radix(hex, 16).ok().map(|byte| (byte, 4))
+    }
+
+    #[derive(Default)]
+    pub struct NameBuilder(Vec<u8>);
+
+    impl NameBuilder {
+        pub fn push_char(&mut self, c: char) {
+            let mut buf = [0; 4];
+            self.0.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
+
+        pub fn push_raw(&mut self, byte: u8) {
+            self.0.push(byte);
+        }
+
+        pub fn finish(self) -> OsString {
+            OsString::from_vec(self.0)
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::ffi::{OsStr, OsString};
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+
+    // calls f for every character of the name, or with the raw code unit for unpaired surrogates
+    pub fn for_each_unit<F: FnMut(Result<char, u16>)>(name: &OsStr, mut f: F) {
+        for c in std::char::decode_utf16(name.encode_wide()) {
+            f(c.map_err(|e| e.unpaired_surrogate()));
+        }
+    }
+
+    pub fn escape_char(c: char) -> String {
+        format!("\\u{{{:04X}}}", c as u32)
+    }
+
+    pub fn escape_raw(unit: u16) -> String {
+        format!("\\u{{{:04X}}}", unit)
+    }
+
+    // parses a '\u{NNNN}' sequence at the start of the text; returns the code unit and length of the sequence
+    pub fn parse_escape(text: &str) -> Option<(u16, usize)> {
+        let rest = text.strip_prefix("\\u{")?;
+        let end = rest.find('}')?;
+        u16::from_str_radix(&rest[..end], 16).ok().map(|unit| (unit, end + 4))
+    }
+
+    #[derive(Default)]
+    pub struct NameBuilder(Vec<u16>);
+
+    impl NameBuilder {
+        pub fn push_char(&mut self, c: char) {
+            let mut buf = [0; 2];
+            self.0.extend_from_slice(c.encode_utf16(&mut buf));
+        }
+
+        pub fn push_raw(&mut self, unit: u16) {
+            self.0.push(unit);
+        }
+
+        pub fn finish(self) -> OsString {
+            OsString::from_wide(&self.0)
+        }
+    }
+}
@@ -0,0 +1,199 @@
+// moving a cursor through the directory tree and the contents panel with the keyboard, and
+// acting on the highlighted entry with a single key:
+//   j/k or down/up   move the cursor
+//   enter            enter the highlighted directory, or preview the highlighted file
+//   x or delete      remove the highlighted entry (to the trash)
+//   r, c, m          start a rename, copy or move of the highlighted entry on the command line
+use std::ffi::OsStr;
+use std::io;
+use std::rc::Rc;
+use crate::command;
+use crate::{ColoredString, ContentsView, CurrentArea, DirectoryRef, DirQuery, FileRef, StateManager};
+
+// how a cursor is moved
+#[derive(Clone, Copy)]
+pub enum CursorMove {
+    Up,
+    Down,
+    First,
+    Last,
+}
+
+impl CursorMove {
+    // the new row of a cursor in a list of count (> 0) rows; a cursor which has not been
+    // placed yet starts at the given row
+    pub fn apply(self, cursor: Option<usize>, start: usize, count: usize) -> usize {
+        match (self, cursor) {
+            (CursorMove::First, _) => 0,
+            (CursorMove::Last, _) => count - 1,
+            (CursorMove::Down, Some(row)) => (row + 1).min(count - 1),
+            (CursorMove::Up, Some(row)) => row.saturating_sub(1),
+            (_, None) => start,
+        }
+    }
+}
+
+// what the highlighted entry of a panel is
+enum Highlighted {
+    // a file of the current directory
+    File(FileRef),
+    Directory(DirectoryRef),
+}
+
+impl<'a> StateManager<'a> {
+    // directories in the order they are shown in the tree (the contents of closed directories
+    // are not shown)
+    fn visible_tree_dirs(&self) -> Vec<DirectoryRef> {
+        fn visit(manager: &StateManager, dir: &DirectoryRef, dirs: &mut Vec<DirectoryRef>) {
+            dirs.push(dir.clone());
+            if manager.closed_dirs.contains(dir) {
+                return;
+            }
+            for child in &dir.borrow().directories {
+                visit(manager, child, dirs);
+            }
+        }
+        let mut dirs = vec![];
+        visit(self, &self.root, &mut dirs);
+        dirs
+    }
+
+    // moves the highlighted row of the tree; it starts from the current directory
+    pub fn move_tree_cursor(&mut self, movement: CursorMove) -> io::Result<()> {
+        let dirs = self.visible_tree_dirs();
+        let position = |target: &DirectoryRef| dirs.iter().position(|dir| Rc::ptr_eq(dir, target));
+        let start = position(&self.curr_dir).unwrap_or(0);
+        let row = movement.apply(self.tree_cursor.as_ref().and_then(position), start, dirs.len());
+        self.tree_cursor = Some(dirs[row].clone());
+
+        // scroll so that the row is visible
+        let area = &mut self.tree;
+        if row < area.curr_pos.y {
+            area.curr_pos.y = row;
+        } else if row >= area.curr_pos.y + area.contents_size().y {
+            area.curr_pos.y = row + 1 - area.contents_size().y;
+        }
+        self.refresh_area(true, false)
+    }
+
+    // adds a column to the lines of the tree for the highlighted row, once the cursor is used
+    pub fn add_tree_cursor(&self, lines: &mut [Vec<ColoredString>]) {
+        let cursor = match &self.tree_cursor {
+            Some(cursor) => cursor,
+            None => return,
+        };
+        for (line, dir) in lines.iter_mut().zip(self.visible_tree_dirs()) {
+            let marker = if Rc::ptr_eq(&dir, cursor) { "> " } else { "  " };
+            line.insert(0, ColoredString::normal(String::from(marker)));
+        }
+    }
+
+    // the entry highlighted in a panel, if any
+    fn highlighted(&self, area: CurrentArea) -> Option<Highlighted> {
+        match area {
+            CurrentArea::Tree => self.tree_cursor.clone()
+                .filter(|dir| self.is_in_tree(dir))
+                .map(Highlighted::Directory),
+            CurrentArea::Contents if self.contents_view == ContentsView::Directory => {
+                let cursor = self.contents_cursor?;
+                let (files, dirs) = self.listed_entries();
+                if cursor < files.len() {
+                    Some(Highlighted::File(files[cursor].clone()))
+                } else {
+                    dirs.get(cursor - files.len()).cloned().map(Highlighted::Directory)
+                }
+            },
+            _ => None,
+        }
+    }
+
+    // enter: enters the highlighted directory or previews the highlighted file
+    pub fn open_highlighted(&mut self, area: CurrentArea) -> io::Result<()> {
+        // search results may be anywhere below the current directory
+        if area == CurrentArea::Contents && self.contents_view == ContentsView::FindResults {
+            let (path, is_dir) = match (&self.find_results, self.contents_cursor) {
+                (Some(results), Some(cursor)) => (results.entries[cursor].path.clone(), results.entries[cursor].is_dir),
+                _ => return Ok(()),
+            };
+            return if is_dir {
+                self.enter_find_result(&path)
+            } else {
+                self.preview_path(&path, None)
+            };
+        }
+
+        match self.highlighted(area) {
+            Some(Highlighted::Directory(dir)) => {
+                self.enter_dir(DirQuery::Disambiguated(dir), OsStr::new(""))?;
+                // the cursor carries on into the directory just entered
+                if area == CurrentArea::Contents {
+                    self.move_contents_cursor(CursorMove::Down)?;
+                }
+                Ok(())
+            },
+            Some(Highlighted::File(file)) => {
+                let path = file.borrow().full_path.clone();
+                self.preview_path(&path, None)
+            },
+            None => Ok(()),
+        }
+    }
+
+    // a single key action on the highlighted entry; returns a command to be completed on the
+    // command line, if the action needs more input
+    pub fn act_on_highlighted(&mut self, area: CurrentArea, key: char) -> io::Result<Option<String>> {
+        let highlighted = match self.highlighted(area) {
+            Some(highlighted) => highlighted,
+            None => return Ok(None),
+        };
+
+        if key == 'x' {
+            match highlighted {
+                Highlighted::File(file) => {
+                    let index = self.curr_dir.borrow().files.iter().position(|f| Rc::ptr_eq(f, &file)).unwrap();
+                    self.remove_file(index, false)?
+                },
+                Highlighted::Directory(dir) => self.remove_dir(DirQuery::Disambiguated(dir), OsStr::new(""))?,
+            }
+            // keep the cursor on the list if the last entry was removed
+            let count = self.current_entries().len();
+            if self.contents_cursor.is_some_and(|cursor| cursor >= count) {
+                self.contents_cursor = count.checked_sub(1);
+                self.refresh_area(false, true)?;
+            }
+            return Ok(None);
+        }
+
+        // the other commands take names in the current directory, so a directory highlighted
+        // in the tree is first shown highlighted in its parent
+        let name = match highlighted {
+            Highlighted::File(file) => file.borrow().name.clone(),
+            Highlighted::Directory(dir) => {
+                let parent = match dir.borrow().parent.clone() {
+                    Some(parent) => parent,
+                    None => {
+                        self.print_error("The root directory cannot be renamed, copied or moved")?;
+                        return Ok(None);
+                    },
+                };
+                if !Rc::ptr_eq(&parent, &self.curr_dir) || self.contents_view != ContentsView::Directory {
+                    self.enter_dir(DirQuery::Disambiguated(parent.clone()), OsStr::new(""))?;
+                }
+                // the filter may hide the directory, in which case the command is still built
+                // from its name but nothing is highlighted in the contents
+                let (files, dirs) = self.listed_entries();
+                if let Some(index) = dirs.iter().position(|d| Rc::ptr_eq(d, &dir)) {
+                    self.set_contents_cursor(files.len() + index)?;
+                }
+                let name = dir.borrow().name.clone();
+                name
+            },
+        };
+        let command = match key {
+            'r' => "rename",
+            'c' => "copy",
+            _ => "move",
+        };
+        Ok(Some(format!("{} {} ", command, command::quote(&name))))
+    }
+}
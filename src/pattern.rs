@@ -0,0 +1,198 @@
+// patterns which select several files or directories in command arguments
+//
+// glob patterns match names:
+//   *       any sequence of characters
+//   ?       any single character
+//   [abc]   any one of the listed characters; ranges such as [a-z] and negation with [!abc] are allowed
+//   \c      the character c literally
+// a glob pattern may also go through subdirectories with '/', where '**' stands for any number of
+// directories (e.g. '**/*.rs'); 're:/expression/' matches names with a regular expression instead,
+// and 're:/expression/i' ignores case
+use std::ffi::OsStr;
+use regex::{Regex, RegexBuilder};
+
+// matches a single name
+pub enum NameMatcher {
+    Glob(Glob),
+    Regex(Box<Regex>),
+}
+
+// one '/'-separated part of a pattern
+pub enum Component {
+    Name(NameMatcher),
+    // '**'
+    AnyDepth,
+}
+
+// a pattern relative to the current directory
+pub struct Pattern {
+    pub components: Vec<Component>,
+}
+
+impl NameMatcher {
+    pub fn is_match(&self, name: &OsStr) -> bool {
+        match self {
+            NameMatcher::Glob(glob) => glob.is_match(name),
+            NameMatcher::Regex(regex) => regex.is_match(&name.to_string_lossy()),
+        }
+    }
+}
+
+// parses an argument as a pattern; returns None if it is a plain name, or a message describing
+// the problem if the pattern is malformed
+pub fn parse(arg: &OsStr) -> Result<Option<Pattern>, String> {
+    let text = arg.to_string_lossy();
+    if let Some(rest) = text.strip_prefix("re:") {
+        let body = rest.strip_prefix('/').and_then(|body| body.rfind('/').map(|end| (&body[..end], &body[end + 1..])));
+        let (expression, flags) = match body {
+            Some((expression, flags)) if flags.is_empty() || flags == "i" => (expression, flags),
+            _ => return Err(String::from("A regular expression must be written as re:/expression/ or re:/expression/i")),
+        };
+        let regex = build_regex(expression, flags == "i")?;
+        return Ok(Some(Pattern { components: vec![Component::Name(NameMatcher::Regex(Box::new(regex)))] }));
+    }
+
+    if !is_glob(arg) {
+        return Ok(None);
+    }
+    let components = text.split('/')
+        .filter(|part| !part.is_empty() && *part != ".")
+        .map(|part| match part {
+            "**" => Component::AnyDepth,
+            part => Component::Name(NameMatcher::Glob(Glob::new(OsStr::new(part)))),
+        })
+        .collect();
+    Ok(Some(Pattern { components }))
+}
+
+// parses an argument which matches single names (without '/'), such as the pattern of 'find';
+// a plain name only matches itself
+pub fn parse_name(arg: &OsStr) -> Result<NameMatcher, String> {
+    match parse(arg)? {
+        Some(Pattern { mut components }) => match components.pop() {
+            Some(Component::Name(matcher)) if components.is_empty() => Ok(matcher),
+            _ => Err(String::from("The pattern must match names, so it cannot contain '/' or '**'")),
+        },
+        None => Ok(NameMatcher::Glob(Glob::new(arg))),
+    }
+}
+
+// compiles a regular expression given in a command; the error is a message for the user
+pub fn build_regex(expression: &str, case_insensitive: bool) -> Result<Regex, String> {
+    RegexBuilder::new(expression)
+        .case_insensitive(case_insensitive)
+        .build()
+        .map_err(|e| {
+            // syntax errors span several lines which point at the problem; only the last one
+            // says what it is
+            let message = e.to_string();
+            let reason = message.lines().last().unwrap_or_default().trim_start_matches("error: ").to_string();
+            format!("Invalid regular expression: {}", reason)
+        })
+}
+
+enum Token {
+    Char(char),
+    AnyChar,
+    AnySequence,
+    // a bracket expression; (first, last) character ranges
+    Class { negated: bool, ranges: Vec<(char, char)> },
+}
+
+pub struct Glob {
+    tokens: Vec<Token>,
+}
+
+// whether an argument should be treated as a glob pattern rather than a plain name
+pub fn is_glob(pattern: &OsStr) -> bool {
+    pattern.to_string_lossy().contains(['*', '?', '['])
+}
+
+impl Glob {
+    pub fn new(pattern: &OsStr) -> Self {
+        let chars: Vec<char> = pattern.to_string_lossy().chars().collect();
+        let mut tokens = vec![];
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '*' => tokens.push(Token::AnySequence),
+                '?' => tokens.push(Token::AnyChar),
+                '\\' if i + 1 < chars.len() => {
+                    i += 1;
+                    tokens.push(Token::Char(chars[i]));
+                },
+                '[' => match Self::parse_class(&chars[i + 1..]) {
+                    Some((token, len)) => {
+                        tokens.push(token);
+                        i += len;
+                    },
+                    // an unterminated bracket is taken literally
+                    None => tokens.push(Token::Char('[')),
+                },
+                c => tokens.push(Token::Char(c)),
+            }
+            i += 1;
+        }
+        Self { tokens }
+    }
+
+    // parses a bracket expression (after the opening bracket); returns it along with how many
+    // characters it took up
+    fn parse_class(chars: &[char]) -> Option<(Token, usize)> {
+        let negated = chars.first() == Some(&'!');
+        let mut i = if negated { 1 } else { 0 };
+        let mut ranges = vec![];
+        // a closing bracket right at the start is part of the set
+        let start = i;
+        while i < chars.len() && (chars[i] != ']' || i == start) {
+            let first = chars[i];
+            if chars.get(i + 1) == Some(&'-') && chars.get(i + 2).is_some_and(|&c| c != ']') {
+                ranges.push((first, chars[i + 2]));
+                i += 3;
+            } else {
+                ranges.push((first, first));
+                i += 1;
+            }
+        }
+        if i < chars.len() {
+            Some((Token::Class { negated, ranges }, i + 1))
+        } else {
+            None
+        }
+    }
+
+    pub fn is_match(&self, name: &OsStr) -> bool {
+        let name: Vec<char> = name.to_string_lossy().chars().collect();
+        let matches_char = |token: &Token, c: char| match token {
+            Token::Char(expected) => *expected == c,
+            Token::AnyChar => true,
+            Token::AnySequence => false,
+            Token::Class { negated, ranges } => ranges.iter().any(|&(first, last)| first <= c && c <= last) != *negated,
+        };
+
+        // on a mismatch, go back to the last '*' and let it take one more character
+        let (mut t, mut n) = (0, 0);
+        let mut backtrack: Option<(usize, usize)> = None;
+        while n < name.len() {
+            match self.tokens.get(t) {
+                Some(Token::AnySequence) => {
+                    backtrack = Some((t, n));
+                    t += 1;
+                },
+                Some(token) if matches_char(token, name[n]) => {
+                    t += 1;
+                    n += 1;
+                },
+                _ => match backtrack {
+                    Some((star, star_n)) => {
+                        t = star + 1;
+                        n = star_n + 1;
+                        backtrack = Some((star, star_n + 1));
+                    },
+                    None => return false,
+                },
+            }
+        }
+        self.tokens[t..].iter().all(|token| matches!(token, Token::AnySequence))
+    }
+}
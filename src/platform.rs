@@ -0,0 +1,416 @@
+// platform specific access to file metadata; the rest of the program goes through the
+// PlatformMetadata trait instead of using the std::os::* extension traits directly
+use std::ffi::{OsStr, OsString};
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// all of the timestamps which may be available for a file; not every platform
+// (or filesystem) supports every kind of timestamp
+#[allow(dead_code)]
+pub struct Timestamps {
+    pub modified: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    pub created: Option<SystemTime>,
+    // time of last status change (unix only)
+    pub changed: Option<SystemTime>,
+}
+
+// what kind of entry a file is, as far as coloring its name goes
+#[derive(Clone, Copy, PartialEq)]
+pub enum FileKind {
+    Directory,
+    Regular,
+    Executable,
+    // executables which run with the owner's or group's permissions
+    Setuid,
+    Setgid,
+    Link,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+}
+
+// not every method is shown in the interface yet, but all are kept for parity between platforms
+#[allow(dead_code)]
+pub trait PlatformMetadata {
+    // size of the file in bytes
+    fn file_size(&self) -> u64;
+    // permissions in the platform's usual notation, e.g. 'drwxr-xr-x' on unix
+    fn permissions_string(&self) -> String;
+    // name of the owning user (or the raw id if it cannot be resolved)
+    fn owner_name(&self) -> String;
+    // name of the owning group (or the raw id if it cannot be resolved)
+    fn group_name(&self) -> String;
+    // inode (or file index) number, if the platform provides it
+    fn inode(&self) -> Option<u64>;
+    // number of hard links to the file, if the platform provides it
+    fn link_count(&self) -> Option<u64>;
+    fn timestamps(&self) -> Timestamps;
+    fn file_kind(&self) -> FileKind;
+}
+
+// whether a file or directory is hidden by the conventions of the platform
+pub fn is_hidden(name: &OsStr, meta: Option<&Metadata>) -> bool {
+    imp::is_hidden(name, meta)
+}
+
+// directory where the program can keep its own data (e.g. ~/.local/share/dirman on unix);
+// None if it cannot be determined
+pub fn data_dir() -> Option<PathBuf> {
+    imp::data_dir().map(|dir| dir.join("dirman"))
+}
+
+// directory of the program's configuration file (e.g. ~/.config/dirman on unix); None if it
+// cannot be determined
+pub fn config_dir() -> Option<PathBuf> {
+    imp::config_dir().map(|dir| dir.join("dirman"))
+}
+
+// directory of the user's trash
+pub fn trash_dir() -> Option<PathBuf> {
+    imp::trash_dir()
+}
+
+// programs used for 'edit' and 'page' when none is set in the environment
+pub const DEFAULT_EDITOR: &str = imp::DEFAULT_EDITOR;
+pub const DEFAULT_PAGER: &str = imp::DEFAULT_PAGER;
+
+// the user's command interpreter
+pub fn shell() -> OsString {
+    imp::shell()
+}
+
+// the raw bytes of a path, for storing it in a file; paths are stored as utf-8 on windows, so
+// names which are not valid unicode cannot be stored there
+pub fn path_bytes(path: &Path) -> Vec<u8> {
+    imp::path_bytes(path)
+}
+
+pub fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    imp::path_from_bytes(bytes)
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::{FileKind, PlatformMetadata, Timestamps};
+    use std::ffi::{CStr, OsStr, OsString};
+    use std::fs::Metadata;
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use std::env;
+
+    // dotfiles are hidden on unix
+    pub fn is_hidden(name: &OsStr, _meta: Option<&Metadata>) -> bool {
+        name.as_bytes().first() == Some(&b'.')
+    }
+
+    // follows the XDG base directory specification
+    pub fn data_dir() -> Option<PathBuf> {
+        match env::var_os("XDG_DATA_HOME").map(PathBuf::from) {
+            Some(dir) if dir.is_absolute() => Some(dir),
+            _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")),
+        }
+    }
+
+    pub fn config_dir() -> Option<PathBuf> {
+        match env::var_os("XDG_CONFIG_HOME").map(PathBuf::from) {
+            Some(dir) if dir.is_absolute() => Some(dir),
+            _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")),
+        }
+    }
+
+    // the home trash of the FreeDesktop trash specification, shared with other programs
+    pub fn trash_dir() -> Option<PathBuf> {
+        data_dir().map(|dir| dir.join("Trash"))
+    }
+
+    pub const DEFAULT_EDITOR: &str = "vi";
+    pub const DEFAULT_PAGER: &str = "less";
+
+    pub fn shell() -> OsString {
+        env::var_os("SHELL").unwrap_or_else(|| OsString::from("/bin/sh"))
+    }
+
+    pub fn path_bytes(path: &Path) -> Vec<u8> {
+        path.as_os_str().as_bytes().to_vec()
+    }
+
+    pub fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+        PathBuf::from(OsString::from_vec(bytes))
+    }
+
+    // converts seconds + nanoseconds since the epoch into a SystemTime; the nanoseconds are
+    // always a positive offset, also before the epoch
+    fn to_system_time(secs: i64, nsecs: i64) -> Option<SystemTime> {
+        let nanos = Duration::from_nanos(nsecs as u64);
+        if secs >= 0 {
+            UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64) + nanos)
+        } else {
+            UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs()))?.checked_add(nanos)
+        }
+    }
+
+    // the user and group databases are read into a buffer which is made larger (up to this
+    // size) for as long as an entry does not fit
+    const MAX_BUFFER_SIZE: usize = 1 << 20;
+
+    // the size suggested for the buffer by sysconf, or a reasonable guess if there is none
+    fn buffer_size(name: libc::c_int) -> usize {
+        match unsafe { libc::sysconf(name) } {
+            size if size > 0 => size as usize,
+            _ => 1024,
+        }
+    }
+
+    // looks up a user name from the user database
+    fn user_name(uid: u32) -> Option<String> {
+        let mut buf = vec![0 as libc::c_char; buffer_size(libc::_SC_GETPW_R_SIZE_MAX)];
+        let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut result: *mut libc::passwd = std::ptr::null_mut();
+        loop {
+            let ret = unsafe {
+                libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result)
+            };
+            if ret == libc::ERANGE && buf.len() < MAX_BUFFER_SIZE {
+                buf.resize(buf.len() * 2, 0);
+                continue;
+            }
+            if ret != 0 || result.is_null() {
+                return None;
+            }
+            let name = unsafe { CStr::from_ptr(pwd.pw_name) };
+            return Some(name.to_string_lossy().into_owned());
+        }
+    }
+
+    // looks up a group name from the group database
+    fn group_name(gid: u32) -> Option<String> {
+        let mut buf = vec![0 as libc::c_char; buffer_size(libc::_SC_GETGR_R_SIZE_MAX)];
+        let mut grp: libc::group = unsafe { std::mem::zeroed() };
+        let mut result: *mut libc::group = std::ptr::null_mut();
+        loop {
+            let ret = unsafe {
+                libc::getgrgid_r(gid, &mut grp, buf.as_mut_ptr(), buf.len(), &mut result)
+            };
+            if ret == libc::ERANGE && buf.len() < MAX_BUFFER_SIZE {
+                buf.resize(buf.len() * 2, 0);
+                continue;
+            }
+            if ret != 0 || result.is_null() {
+                return None;
+            }
+            let name = unsafe { CStr::from_ptr(grp.gr_name) };
+            return Some(name.to_string_lossy().into_owned());
+        }
+    }
+
+    impl PlatformMetadata for Metadata {
+        fn file_size(&self) -> u64 {
+            self.size()
+        }
+
+        fn permissions_string(&self) -> String {
+            let mode = self.mode();
+            let file_type = self.file_type();
+            let kind = if file_type.is_dir() {
+                'd'
+            } else if file_type.is_symlink() {
+                'l'
+            } else if file_type.is_fifo() {
+                'p'
+            } else if file_type.is_socket() {
+                's'
+            } else if file_type.is_char_device() {
+                'c'
+            } else if file_type.is_block_device() {
+                'b'
+            } else {
+                '-'
+            };
+
+            let mut s = String::with_capacity(10);
+            s.push(kind);
+            // the setuid, setgid and sticky bits take the place of the execute permission of the
+            // owner, group and others, in lowercase if that permission is there as well
+            for (shift, special, letter) in &[(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+                let bits = (mode >> shift) & 0o7;
+                s.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+                s.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+                s.push(match (mode & special != 0, bits & 0o1 != 0) {
+                    (true, true) => *letter,
+                    (true, false) => letter.to_ascii_uppercase(),
+                    (false, true) => 'x',
+                    (false, false) => '-',
+                });
+            }
+            s
+        }
+
+        fn owner_name(&self) -> String {
+            user_name(self.uid()).unwrap_or_else(|| self.uid().to_string())
+        }
+
+        fn group_name(&self) -> String {
+            group_name(self.gid()).unwrap_or_else(|| self.gid().to_string())
+        }
+
+        fn inode(&self) -> Option<u64> {
+            Some(self.ino())
+        }
+
+        fn link_count(&self) -> Option<u64> {
+            Some(self.nlink())
+        }
+
+        fn timestamps(&self) -> Timestamps {
+            Timestamps {
+                modified: to_system_time(self.mtime(), self.mtime_nsec()),
+                accessed: to_system_time(self.atime(), self.atime_nsec()),
+                created: self.created().ok(),
+                changed: to_system_time(self.ctime(), self.ctime_nsec()),
+            }
+        }
+
+        fn file_kind(&self) -> FileKind {
+            let file_type = self.file_type();
+            let mode = self.mode();
+            if file_type.is_dir() {
+                FileKind::Directory
+            } else if file_type.is_symlink() {
+                FileKind::Link
+            } else if file_type.is_fifo() {
+                FileKind::Fifo
+            } else if file_type.is_socket() {
+                FileKind::Socket
+            } else if file_type.is_block_device() {
+                FileKind::BlockDevice
+            } else if file_type.is_char_device() {
+                FileKind::CharDevice
+            } else if mode & 0o4000 != 0 {
+                FileKind::Setuid
+            } else if mode & 0o2000 != 0 {
+                FileKind::Setgid
+            } else if mode & 0o111 != 0 {
+                FileKind::Executable
+            } else {
+                FileKind::Regular
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::{FileKind, PlatformMetadata, Timestamps};
+    use std::ffi::{OsStr, OsString};
+    use std::fs::Metadata;
+    use std::os::windows::fs::MetadataExt;
+    use std::path::{Path, PathBuf};
+    use std::env;
+
+    const FILE_ATTRIBUTE_READONLY: u32 = 0x1;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+    const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x10;
+    const FILE_ATTRIBUTE_ARCHIVE: u32 = 0x20;
+    const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+
+    // files are hidden on windows by their attributes rather than by name
+    pub fn is_hidden(_name: &OsStr, meta: Option<&Metadata>) -> bool {
+        meta.is_some_and(|meta| meta.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+    }
+
+    pub fn data_dir() -> Option<PathBuf> {
+        env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    }
+
+    pub fn config_dir() -> Option<PathBuf> {
+        env::var_os("APPDATA").map(PathBuf::from)
+    }
+
+    // the recycle bin can only be used through the shell API, so a trash of the program's
+    // own (laid out the same as on other platforms) is used instead
+    pub fn trash_dir() -> Option<PathBuf> {
+        super::data_dir().map(|dir| dir.join("Trash"))
+    }
+
+    pub const DEFAULT_EDITOR: &str = "notepad";
+    pub const DEFAULT_PAGER: &str = "more";
+
+    pub fn shell() -> OsString {
+        env::var_os("COMSPEC").unwrap_or_else(|| OsString::from("cmd.exe"))
+    }
+
+    pub fn path_bytes(path: &Path) -> Vec<u8> {
+        path.to_string_lossy().into_owned().into_bytes()
+    }
+
+    pub fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+        PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    impl PlatformMetadata for Metadata {
+        fn file_size(&self) -> u64 {
+            MetadataExt::file_size(self)
+        }
+
+        // same notation as the 'Mode' column of powershell's Get-ChildItem
+        fn permissions_string(&self) -> String {
+            let attrs = self.file_attributes();
+            let flag = |bit: u32, c: char| if attrs & bit != 0 { c } else { '-' };
+            [
+                flag(FILE_ATTRIBUTE_DIRECTORY, 'd'),
+                flag(FILE_ATTRIBUTE_ARCHIVE, 'a'),
+                flag(FILE_ATTRIBUTE_READONLY, 'r'),
+                flag(FILE_ATTRIBUTE_HIDDEN, 'h'),
+                flag(FILE_ATTRIBUTE_SYSTEM, 's'),
+                flag(FILE_ATTRIBUTE_REPARSE_POINT, 'l'),
+            ].iter().collect()
+        }
+
+        // owner information requires the win32 security API; not available here
+        fn owner_name(&self) -> String {
+            String::from("-")
+        }
+
+        fn group_name(&self) -> String {
+            String::from("-")
+        }
+
+        // the file index and link count are only available through the win32 API (or unstable
+        // parts of the standard library)
+        fn inode(&self) -> Option<u64> {
+            None
+        }
+
+        fn link_count(&self) -> Option<u64> {
+            None
+        }
+
+        fn timestamps(&self) -> Timestamps {
+            Timestamps {
+                modified: self.modified().ok(),
+                accessed: self.accessed().ok(),
+                created: self.created().ok(),
+                changed: None,
+            }
+        }
+
+        // whether a file is executable depends on its extension on windows, which is left to
+        // the coloring by extension
+        fn file_kind(&self) -> FileKind {
+            let file_type = self.file_type();
+            if file_type.is_dir() {
+                FileKind::Directory
+            } else if file_type.is_symlink() {
+                FileKind::Link
+            } else {
+                FileKind::Regular
+            }
+        }
+    }
+}
@@ -0,0 +1,357 @@
+// previews of the contents and metadata of a file
+//
+// 'view <file> [text|hex|meta]' shows the first lines of a text file, a hex dump of a binary file
+// or every piece of metadata about a file or directory; the preview is shown in the contents
+// panel, or in a third pane on the right when it is turned on with 'preview-pane on', in which
+// case the pane also follows the highlighted entry of the contents panel; source files in a
+// known language are highlighted once the highlighter has got through them
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+use chrono::{DateTime, Local};
+use crate::theme::Color;
+use crate::grep;
+use crate::highlight::Language;
+use crate::names;
+use crate::platform::PlatformMetadata;
+use crate::{ColoredString, ContentsView, StateManager, Vector2};
+
+// text files are shown up to this many lines
+const TEXT_LINES: usize = 1000;
+// and only this much of them is read
+const TEXT_BYTES: u64 = 1024 * 1024;
+// binary files are dumped up to this many bytes
+const HEX_BYTES: u64 = 64 * 1024;
+const HEX_ROW_LEN: usize = 16;
+const TAB_WIDTH: usize = 4;
+// lines above the contents of the file: its name and an underline
+const HEADER_LINES: usize = 2;
+
+// tells previews apart so that highlighting finished for an earlier one is not applied
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PreviewMode {
+    Text,
+    Hex,
+    Meta,
+}
+
+// a file (or directory) being previewed
+pub struct Preview {
+    pub path: PathBuf,
+    pub id: u64,
+    // the rendered preview, kept so that it is not read again every time the screen is redrawn
+    lines: Vec<Vec<ColoredString>>,
+    // the language and lines of a text preview which is still to be highlighted
+    unhighlighted: Option<(Language, Vec<String>)>,
+}
+
+impl PreviewMode {
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "text" => Some(PreviewMode::Text),
+            "hex" => Some(PreviewMode::Hex),
+            "meta" => Some(PreviewMode::Meta),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            PreviewMode::Text => "text",
+            PreviewMode::Hex => "hex",
+            PreviewMode::Meta => "meta",
+        }
+    }
+}
+
+impl Preview {
+    // reads a preview of a file; without a mode, binary files show a hex dump, other files their
+    // text, and anything which is not a regular file (such as a directory, or a FIFO which reading
+    // would wait on) its metadata; the error is a message for the user
+    pub fn load(path: &Path, mode: Option<PreviewMode>) -> Result<Self, String> {
+        let describe = |error: io::Error| format!("Cannot preview '{}': {}",
+            names::display_name(path.file_name().unwrap_or(path.as_os_str())), crate::scan::describe_error(&error));
+        let meta = path.symlink_metadata().map_err(describe)?;
+        // symbolic links are previewed as what they point to
+        let target_type = if meta.file_type().is_symlink() {
+            path.metadata().ok().map(|target| target.file_type())
+        } else {
+            Some(meta.file_type())
+        };
+        let is_dir = target_type.is_some_and(|file_type| file_type.is_dir());
+        let is_file = target_type.is_some_and(|file_type| file_type.is_file());
+
+        let mode = match mode {
+            Some(PreviewMode::Meta) => PreviewMode::Meta,
+            Some(_) if is_dir => return Err(String::from("Directories can only be previewed with 'meta'")),
+            Some(_) if !is_file => return Err(String::from("Only regular files can be previewed as text or hex; use 'meta'")),
+            Some(mode) => mode,
+            None if !is_file => PreviewMode::Meta,
+            None => {
+                let head = read_start(path, grep::BINARY_CHECK_LEN as u64).map_err(describe)?;
+                if grep::looks_binary(&head) { PreviewMode::Hex } else { PreviewMode::Text }
+            },
+        };
+
+        let mut lines = vec![
+            vec![ColoredString::colored(format!("{} ({})", names::display_name(path.file_name().unwrap_or(path.as_os_str())), mode.name()), Color::Header)],
+        ];
+        let underline = "‾".repeat(lines[0][0].string.chars().count());
+        lines.push(vec![ColoredString::colored(underline, Color::Header)]);
+        let mut unhighlighted = None;
+        match mode {
+            PreviewMode::Text => {
+                let bytes = read_start(path, TEXT_BYTES).map_err(describe)?;
+                let mut text = text_lines(&bytes);
+                let too_many_lines = text.len() > TEXT_LINES;
+                text.truncate(TEXT_LINES);
+                lines.extend(text.iter().enumerate().map(|(i, line)| vec![
+                    ColoredString::colored(format!("{:>5} ", i + 1), Color::Muted),
+                    ColoredString::normal(line.clone()),
+                ]));
+                if too_many_lines {
+                    lines.push(vec![ColoredString::colored(format!("(only the first {} lines are shown)", TEXT_LINES), Color::Muted)]);
+                } else if bytes.len() as u64 == TEXT_BYTES {
+                    lines.push(vec![ColoredString::colored(format!("(only the first {} are shown)", crate::file_size_to_str(TEXT_BYTES)), Color::Muted)]);
+                }
+                unhighlighted = Language::of(path).map(|language| (language, text));
+            },
+            PreviewMode::Hex => lines.extend(hex_lines(&read_start(path, HEX_BYTES).map_err(describe)?)),
+            PreviewMode::Meta => lines.extend(meta_lines(path, &meta)),
+        }
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        Ok(Self { path: path.to_path_buf(), id, lines, unhighlighted })
+    }
+
+    // replaces the plain lines of text with highlighted ones
+    pub fn set_highlighted(&mut self, highlighted: Vec<Vec<ColoredString>>) {
+        for (line, pieces) in self.lines[HEADER_LINES..].iter_mut().zip(highlighted) {
+            line.truncate(1);
+            line.extend(pieces);
+        }
+    }
+}
+
+// reads up to a number of bytes from the start of a file
+fn read_start(path: &Path, len: u64) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    File::open(path)?.take(len).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+// the lines of a text file, up to one more than are shown; tabs are expanded and other control
+// characters replaced
+fn text_lines(bytes: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(bytes)
+        .lines()
+        .take(TEXT_LINES + 1)
+        .map(|line| line.chars()
+            .flat_map(|c| match c {
+                '\t' => vec![' '; TAB_WIDTH],
+                c if c.is_control() => vec!['?'],
+                c => vec![c],
+            })
+            .collect())
+        .collect()
+}
+
+// offset, bytes in hex and the printable ascii characters, e.g.
+// '00000010  68 65 6c 6c 6f 0a 00 00  00 00 00 00 00 00 00 00  |hello...........|'
+fn hex_lines(bytes: &[u8]) -> Vec<Vec<ColoredString>> {
+    let mut lines = vec![];
+    for (row, chunk) in bytes.chunks(HEX_ROW_LEN).enumerate() {
+        let mut hex = String::new();
+        for i in 0..HEX_ROW_LEN {
+            match chunk.get(i) {
+                Some(byte) => hex.push_str(&format!("{:02x} ", byte)),
+                None => hex.push_str("   "),
+            }
+            if i == HEX_ROW_LEN / 2 - 1 {
+                hex.push(' ');
+            }
+        }
+        let ascii: String = chunk.iter()
+            .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+            .collect();
+        lines.push(vec![
+            ColoredString::colored(format!("{:08x}  ", row * HEX_ROW_LEN), Color::Muted),
+            ColoredString::normal(hex),
+            ColoredString::colored(format!(" |{}|", ascii), Color::Muted),
+        ]);
+    }
+    if bytes.len() as u64 == HEX_BYTES {
+        lines.push(vec![ColoredString::colored(format!("(only the first {} are shown)", crate::file_size_to_str(HEX_BYTES)), Color::Muted)]);
+    }
+    lines
+}
+
+// a sheet of everything known about a file; meta is the metadata of the entry itself (not of
+// what it links to)
+fn meta_lines(path: &Path, meta: &fs::Metadata) -> Vec<Vec<ColoredString>> {
+    let file_type = meta.file_type();
+    let kind = if file_type.is_dir() {
+        String::from("directory")
+    } else if file_type.is_symlink() {
+        match fs::read_link(path) {
+            Ok(target) => format!("symbolic link to '{}'", names::display_name(target.as_os_str())),
+            Err(_) => String::from("symbolic link"),
+        }
+    } else if file_type.is_file() {
+        String::from("file")
+    } else {
+        String::from("special file")
+    };
+    let optional = |value: Option<u64>| value.map_or(String::from("not available"), |value| value.to_string());
+    let timestamp = |time: Option<SystemTime>| time.map_or(String::from("not available"), |time| {
+        DateTime::<Local>::from(time).format("%Y-%m-%d %H:%M:%S").to_string()
+    });
+    let timestamps = meta.timestamps();
+
+    let rows = vec![
+        ("Path", names::display_name(path.as_os_str())),
+        ("Type", kind),
+        ("Size", format!("{} bytes ({})", meta.file_size(), crate::file_size_to_str(meta.file_size()))),
+        ("Permissions", meta.permissions_string()),
+        ("Owner", meta.owner_name()),
+        ("Group", meta.group_name()),
+        ("Inode", optional(meta.inode())),
+        ("Links", optional(meta.link_count())),
+        ("Modified", timestamp(timestamps.modified)),
+        ("Accessed", timestamp(timestamps.accessed)),
+        ("Created", timestamp(timestamps.created)),
+        ("Changed", timestamp(timestamps.changed)),
+    ];
+    rows.into_iter()
+        .map(|(label, value)| vec![
+            ColoredString::colored(format!("{:13}", label), Color::Muted),
+            ColoredString::normal(value),
+        ])
+        .collect()
+}
+
+impl<'a> StateManager<'a> {
+    // view <file|directory|#N> [text|hex|meta]
+    pub fn view(&mut self, args: &[std::ffi::OsString]) -> io::Result<()> {
+        let mode = match args.get(1).map(|mode| mode.to_str().and_then(PreviewMode::parse)) {
+            Some(Some(mode)) => Some(mode),
+            Some(None) => return self.print_error("The preview can be one of text, hex or meta"),
+            None => None,
+        };
+
+        let path = match self.find_result(&args[0]) {
+            Some(Ok(path)) => path,
+            Some(Err(message)) => return self.print_error(&message),
+            None => {
+                let curr_dir = self.curr_dir.borrow();
+                let found = curr_dir.files.iter().map(|f| f.borrow().full_path.clone())
+                    .chain(curr_dir.directories.iter().map(|d| d.borrow().full_path.clone()))
+                    .find(|path| path.file_name() == Some(args[0].as_os_str()));
+                drop(curr_dir);
+                match found {
+                    Some(path) => path,
+                    None => return self.print_error("File or directory to preview does not exist"),
+                }
+            },
+        };
+
+        self.preview_path(&path, mode)
+    }
+
+    // previews a file in the preview pane if it is open, otherwise in the contents panel
+    pub fn preview_path(&mut self, path: &Path, mode: Option<PreviewMode>) -> io::Result<()> {
+        match Preview::load(path, mode) {
+            Ok(preview) => self.set_preview(preview),
+            Err(message) => return self.print_error(&message),
+        }
+        if let Some(area) = &mut self.preview_area {
+            area.curr_pos = Vector2 { x: 0, y: 0 };
+        } else {
+            self.contents_view = ContentsView::Preview;
+            self.contents_cursor = None;
+            self.dir_contents.curr_pos = Vector2 { x: 0, y: 0 };
+        }
+        self.refresh_area(false, true)
+    }
+
+    // shows the highlighted entry of the contents panel in the preview pane, if it is open
+    pub fn preview_highlighted(&mut self) -> io::Result<()> {
+        if self.preview_area.is_none() {
+            return Ok(());
+        }
+        let path = match (self.contents_view, self.contents_cursor) {
+            (ContentsView::Directory, Some(cursor)) => self.current_entries().get(cursor).map(|(_, path)| path.clone()),
+            (ContentsView::FindResults, Some(cursor)) => self.find_results.as_ref()
+                .and_then(|results| results.entries.get(cursor))
+                .map(|result| result.path.clone()),
+            _ => None,
+        };
+        if path.is_none() || self.preview.as_ref().map(|preview| &preview.path) == path.as_ref() {
+            return Ok(());
+        }
+        // an entry which cannot be previewed leaves the pane as it was
+        if let Some(Ok(preview)) = path.map(|path| Preview::load(&path, None)) {
+            self.set_preview(preview);
+            if let Some(area) = &mut self.preview_area {
+                area.curr_pos = Vector2 { x: 0, y: 0 };
+            }
+            self.refresh_area(false, true)?;
+        }
+        Ok(())
+    }
+
+    // replaces the preview, handing its text to the highlighter if it is in a known language
+    fn set_preview(&mut self, mut preview: Preview) {
+        if let Some((language, lines)) = preview.unhighlighted.take() {
+            self.highlighter.request(preview.id, language, lines);
+        }
+        self.preview = Some(preview);
+    }
+
+    // applies highlighting finished in the background to the preview; returns whether the
+    // preview was redrawn
+    pub fn apply_highlighting(&mut self) -> io::Result<bool> {
+        let highlighted = match self.highlighter.try_result() {
+            Some(highlighted) => highlighted,
+            None => return Ok(false),
+        };
+        match &mut self.preview {
+            Some(preview) if preview.id == highlighted.id => preview.set_highlighted(highlighted.lines),
+            _ => return Ok(false),
+        }
+        if self.preview_area.is_some() || self.contents_view == ContentsView::Preview {
+            self.refresh_area(false, true)?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    // preview-pane on|off
+    pub fn set_preview_pane(&mut self, open: bool) -> io::Result<()> {
+        if open == self.preview_area.is_some() {
+            return Ok(());
+        }
+        self.preview_area = if open {
+            Some(crate::ScrollableArea::new())
+        } else {
+            None
+        };
+        // a preview shown in the contents panel moves over to the pane
+        if open && self.contents_view == ContentsView::Preview {
+            self.contents_view = ContentsView::Directory;
+        }
+        self.layout();
+        crate::draw_outline(self, crate::CurrentArea::Command)?;
+        self.refresh_area(true, true)
+    }
+
+    pub fn load_preview_contents(&self) -> Vec<Vec<ColoredString>> {
+        match &self.preview {
+            Some(preview) => preview.lines.clone(),
+            None => vec![vec![ColoredString::normal(String::from("Nothing to preview; use 'view <file>' or highlight a file"))]],
+        }
+    }
+}
@@ -0,0 +1,480 @@
+// renaming the entries of the current directory which match a regular expression
+//
+// the part of each name matched by the expression is replaced after a template, in which
+//   {1}, {name}          the text captured by a group (by number or by name; {0} is the whole match)
+//   {1:upper}, {1:lower} the same, converted to upper or lower case
+//   {n}, {n:03}          the number of the entry (starting from 1), optionally padded with zeros
+//   {{, }}               a literal brace
+// all renames are checked before anything is changed, and are made through temporary names so
+// that names can be swapped and nothing is left half done if one of them fails
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process;
+use crate::theme::Color;
+use regex::Regex;
+use crate::{names, pattern, scan};
+use crate::{ColoredString, ContentsView, StateManager, Vector2};
+use crate::journal::Change;
+use crate::marks::{self, Summary};
+
+enum Case {
+    Unchanged,
+    Upper,
+    Lower,
+}
+
+enum Piece {
+    Text(String),
+    Group { index: usize, case: Case },
+    Counter { width: usize },
+}
+
+// a parsed replacement template
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+// a single rename in a plan
+pub struct PlannedRename {
+    pub from: OsString,
+    pub to: OsString,
+    // why the rename cannot be made, e.g. because another entry would get the same name
+    pub problem: Option<String>,
+    // whether the rename is part of a cycle of names (e.g. two names being swapped)
+    pub in_cycle: bool,
+}
+
+// the renames to make in a directory, shown for confirmation before they are made
+pub struct RenamePlan {
+    pub dir: PathBuf,
+    pub renames: Vec<PlannedRename>,
+}
+
+impl Template {
+    // parses a template for the captures of a regular expression; the error is a message for
+    // the user
+    pub fn new(text: &str, regex: &Regex) -> Result<Self, String> {
+        let mut pieces = vec![];
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                },
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                },
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => return Err(format!("Missing '}}' after '{{{}' in the replacement", field)),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(Self::parse_field(&field, regex)?);
+                },
+                '}' => return Err(String::from("A literal '}' must be written as '}}' in the replacement")),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            pieces.push(Piece::Text(literal));
+        }
+        Ok(Self { pieces })
+    }
+
+    // parses what is between the braces of '{group:conversion}'
+    fn parse_field(field: &str, regex: &Regex) -> Result<Piece, String> {
+        let (key, format) = match field.split_once(':') {
+            Some((key, format)) => (key, Some(format)),
+            None => (field, None),
+        };
+
+        if key == "n" {
+            return match format {
+                None => Ok(Piece::Counter { width: 0 }),
+                Some(format) => format.parse::<usize>().map(|width| Piece::Counter { width })
+                    .map_err(|_| format!("'{{{}}}' must be written as {{n}} or with a width such as {{n:03}}", field)),
+            };
+        }
+
+        let index = match key.parse::<usize>() {
+            Ok(index) if index < regex.captures_len() => index,
+            Ok(_) => return Err(format!("The expression has no group {}", key)),
+            Err(_) => regex.capture_names().position(|name| name == Some(key))
+                .ok_or_else(|| format!("The expression has no group named '{}'", key))?,
+        };
+        let case = match format {
+            None => Case::Unchanged,
+            Some("upper") => Case::Upper,
+            Some("lower") => Case::Lower,
+            Some(other) => return Err(format!("Unknown conversion '{}'; use upper or lower", other)),
+        };
+        Ok(Piece::Group { index, case })
+    }
+
+    // the replacement for a match, for the entry with the given number
+    fn expand(&self, captures: &regex::Captures, number: usize) -> String {
+        let mut expanded = String::new();
+        for piece in &self.pieces {
+            match piece {
+                Piece::Text(text) => expanded.push_str(text),
+                Piece::Group { index, case } => {
+                    let text = captures.get(*index).map_or("", |m| m.as_str());
+                    match case {
+                        Case::Unchanged => expanded.push_str(text),
+                        Case::Upper => expanded.push_str(&text.to_uppercase()),
+                        Case::Lower => expanded.push_str(&text.to_lowercase()),
+                    }
+                },
+                Piece::Counter { width } => expanded.push_str(&format!("{:0width$}", number, width = width)),
+            }
+        }
+        expanded
+    }
+}
+
+impl RenamePlan {
+    // works out the new names of the entries in a directory (given by name) which match an
+    // expression, and checks them against each other and the other entries
+    pub fn new(dir: PathBuf, entries: &[OsString], regex: &Regex, template: &Template) -> Self {
+        let mut renames = vec![];
+        let mut number = 0;
+        // names which are not valid unicode cannot be matched
+        for (name, text) in entries.iter().filter_map(|name| name.to_str().map(|text| (name, text))) {
+            let captures = match regex.captures(text) {
+                Some(captures) => captures,
+                None => continue,
+            };
+            number += 1;
+            let whole = captures.get(0).unwrap();
+            let new_name = format!("{}{}{}", &text[..whole.start()], template.expand(&captures, number), &text[whole.end()..]);
+            if new_name != text {
+                renames.push(PlannedRename { from: name.clone(), to: OsString::from(new_name), problem: None, in_cycle: false });
+            }
+        }
+
+        let sources: HashSet<&OsStr> = renames.iter().map(|r| r.from.as_os_str()).collect();
+        let existing: HashSet<&OsStr> = entries.iter().map(OsString::as_os_str).collect();
+        let mut targets: HashMap<&OsStr, usize> = HashMap::new();
+        for rename in &renames {
+            *targets.entry(rename.to.as_os_str()).or_default() += 1;
+        }
+        let problems: Vec<Option<String>> = renames.iter()
+            .map(|rename| {
+                if !crate::is_valid_name(&rename.to) {
+                    Some(String::from("the new name must not be empty or contain path separators"))
+                } else if targets[rename.to.as_os_str()] > 1 {
+                    Some(String::from("another entry would get the same name"))
+                } else if existing.contains(rename.to.as_os_str()) && !sources.contains(rename.to.as_os_str()) {
+                    Some(String::from("an entry with this name already exists"))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        // following the new names from one rename to the next leads back to the start for
+        // renames in a cycle
+        let next: HashMap<&OsStr, &OsStr> = renames.iter().map(|r| (r.from.as_os_str(), r.to.as_os_str())).collect();
+        let cycles: Vec<bool> = renames.iter()
+            .map(|rename| {
+                let mut name = rename.to.as_os_str();
+                for _ in 0..renames.len() {
+                    if name == rename.from {
+                        return true;
+                    }
+                    match next.get(name) {
+                        Some(to) => name = to,
+                        None => return false,
+                    }
+                }
+                false
+            })
+            .collect();
+
+        for ((rename, problem), in_cycle) in renames.iter_mut().zip(problems).zip(cycles) {
+            rename.problem = problem;
+            rename.in_cycle = in_cycle;
+        }
+        Self { dir, renames }
+    }
+
+    fn problem_count(&self) -> usize {
+        self.renames.iter().filter(|r| r.problem.is_some()).count()
+    }
+}
+
+impl<'a> StateManager<'a> {
+    // works out the renames for 'rename-batch' and shows them, asking for confirmation if they
+    // can all be made
+    pub fn plan_rename_batch(&mut self, expression: &OsStr, replacement: &OsStr) -> io::Result<bool> {
+        let (expression, replacement) = match (expression.to_str(), replacement.to_str()) {
+            (Some(expression), Some(replacement)) => (expression, replacement),
+            _ => {
+                self.print_error("The expression and the replacement must be valid unicode")?;
+                return Ok(false);
+            },
+        };
+        let regex = match pattern::build_regex(expression, false) {
+            Ok(regex) => regex,
+            Err(message) => {
+                self.print_error(&message)?;
+                return Ok(false);
+            },
+        };
+        let template = match Template::new(replacement, &regex) {
+            Ok(template) => template,
+            Err(message) => {
+                self.print_error(&message)?;
+                return Ok(false);
+            },
+        };
+
+        let entries: Vec<OsString> = {
+            let curr_dir = self.curr_dir.borrow();
+            curr_dir.files.iter().map(|f| f.borrow().name.clone())
+                .chain(curr_dir.directories.iter().map(|d| d.borrow().name.clone()))
+                .collect()
+        };
+        let plan = RenamePlan::new(self.curr_dir.borrow().full_path.clone(), &entries, &regex, &template);
+        if plan.renames.is_empty() {
+            self.print_error("No names in the current directory would change")?;
+            return Ok(false);
+        }
+
+        let count = plan.renames.len();
+        let problems = plan.problem_count();
+        self.rename_plan = Some(plan);
+        self.contents_view = ContentsView::RenamePreview;
+        self.dir_contents.curr_pos = Vector2 { x: 0, y: 0 };
+        self.refresh_area(false, true)?;
+
+        if problems > 0 {
+            self.print_error(&format!("{} of {} renames cannot be made; nothing was renamed", problems, count))?;
+            return Ok(false);
+        }
+        self.print_error(&format!("Rename {}? yes/no", marks::entry_count(count)))?;
+        Ok(true)
+    }
+
+    // makes the renames of the confirmed plan; either all of them are made or none are
+    pub fn apply_rename_plan(&mut self) -> io::Result<()> {
+        let plan = match self.rename_plan.take() {
+            Some(plan) => plan,
+            None => return Ok(()),
+        };
+        self.contents_view = ContentsView::Directory;
+
+        // every entry is first moved out of the way under a temporary name, so that no new name
+        // is still taken by an entry which is yet to be renamed
+        let mut steps = vec![];
+        let mut temp_number = 0;
+        for rename in &plan.renames {
+            let temp = loop {
+                temp_number += 1;
+                let temp = plan.dir.join(format!(".dirman-rename-{}-{}", process::id(), temp_number));
+                if temp.symlink_metadata().is_err() {
+                    break temp;
+                }
+            };
+            steps.push((plan.dir.join(&rename.from), temp.clone()));
+            steps.push((temp, plan.dir.join(&rename.to)));
+        }
+        let mut order: Vec<usize> = (0..steps.len()).step_by(2).collect();
+        order.extend((1..steps.len()).step_by(2));
+
+        let mut done = vec![];
+        for &index in &order {
+            let (from, to) = &steps[index];
+            // renaming replaces an existing file, so check that nothing took the name since
+            let result = if to.symlink_metadata().is_ok() {
+                Err(format!("'{}' already exists", names::display_name(to.file_name().unwrap())))
+            } else {
+                fs::rename(from, to).map_err(|e| scan::describe_error(&e))
+            };
+            if let Err(reason) = result {
+                for &index in done.iter().rev() {
+                    let (from, to): &(PathBuf, PathBuf) = &steps[index];
+                    let _ = fs::rename(to, from);
+                }
+                // the entry that failed is named after its original name, even for the second step
+                let name = &plan.renames[index / 2].from;
+                self.refresh_area(false, true)?;
+                return self.print_error(&format!("Nothing was renamed; could not rename '{}': {}", names::display_name(name), reason));
+            }
+            done.push(index);
+        }
+
+        let mut changes = vec![];
+        for &index in &order {
+            let (from, to) = steps[index].clone();
+            self.relocate_in_tree(&from, &to);
+            changes.push(Change::Moved { from, to });
+        }
+        let summary = Summary { done: plan.renames.len(), failures: vec![] };
+        self.finish_batch(summary, changes, "rename", "Renamed", "")
+    }
+
+    // drops a plan which was not confirmed
+    pub fn discard_rename_plan(&mut self) -> io::Result<()> {
+        if self.rename_plan.take().is_some() && self.contents_view == ContentsView::RenamePreview {
+            self.contents_view = ContentsView::Directory;
+            self.refresh_area(false, true)?;
+        }
+        Ok(())
+    }
+
+    // lists the old and new names of the planned renames
+    pub fn load_rename_preview(&self) -> Vec<Vec<ColoredString>> {
+        let plan = match &self.rename_plan {
+            Some(plan) => plan,
+            None => return vec![],
+        };
+        let width = plan.renames.iter()
+            .map(|r| names::display_name(&r.from).chars().count())
+            .fold(8, usize::max);
+
+        let mut contents = vec![
+            vec![ColoredString::colored(format!("{:width$}    New Name", "Old Name", width = width), Color::Header)],
+            vec![ColoredString::colored(format!("{:width$}    ‾‾‾‾‾‾‾‾", "‾‾‾‾‾‾‾‾", width = width), Color::Header)],
+        ];
+        for rename in &plan.renames {
+            let padding = width - names::display_name(&rename.from).chars().count();
+            let mut line = names::colored_name(&rename.from, None);
+            line.push(ColoredString::normal(format!("{} →  ", " ".repeat(padding))));
+            let color = if rename.problem.is_some() { Some(Color::Error) } else { None };
+            line.extend(names::colored_name(&rename.to, color));
+            if let Some(problem) = &rename.problem {
+                line.push(ColoredString::colored(format!("  ({})", problem), Color::Error));
+            } else if rename.in_cycle {
+                line.push(ColoredString::colored(String::from("  (cycle)"), Color::Muted));
+            }
+            contents.push(line);
+        }
+        contents
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the new name of each entry which matches the expression
+    fn expand(expression: &str, template: &str, names: &[&str]) -> Vec<String> {
+        let regex = Regex::new(expression).unwrap();
+        let template = Template::new(template, &regex).unwrap();
+        names.iter().enumerate()
+            .map(|(i, name)| template.expand(&regex.captures(name).unwrap(), i + 1))
+            .collect()
+    }
+
+    fn plan(expression: &str, template: &str, entries: &[&str]) -> RenamePlan {
+        let regex = Regex::new(expression).unwrap();
+        let template = Template::new(template, &regex).unwrap();
+        let entries: Vec<OsString> = entries.iter().map(OsString::from).collect();
+        RenamePlan::new(PathBuf::from("dir"), &entries, &regex, &template)
+    }
+
+    fn template_error(expression: &str, template: &str) -> String {
+        Template::new(template, &Regex::new(expression).unwrap()).err().unwrap()
+    }
+
+    #[test]
+    fn counters() {
+        assert_eq!(expand(".*", "{n}", &["a", "b"]), ["1", "2"]);
+        assert_eq!(expand(".*", "img{n:03}", &["a", "b"]), ["img001", "img002"]);
+        assert_eq!(expand(".*", "{n:2}_{0}", &["a"]), ["01_a"]);
+        assert_eq!(template_error(".*", "{n:x}"), "'{n:x}' must be written as {n} or with a width such as {n:03}");
+    }
+
+    #[test]
+    fn groups() {
+        assert_eq!(expand(r"(\w+)\.(\w+)", "{2}.{1}", &["notes.txt"]), ["txt.notes"]);
+        assert_eq!(expand(r"(?P<stem>\w+)\.(?P<ext>\w+)", "{stem:upper}.{ext:lower}", &["Notes.TXT"]), ["NOTES.txt"]);
+        assert_eq!(expand("(a)|(b)", "[{1}{2}]", &["b"]), ["[b]"]);
+        assert_eq!(template_error("(a)", "{2}"), "The expression has no group 2");
+        assert_eq!(template_error("(a)", "{stem}"), "The expression has no group named 'stem'");
+        assert_eq!(template_error("(a)", "{1:title}"), "Unknown conversion 'title'; use upper or lower");
+    }
+
+    #[test]
+    fn braces() {
+        assert_eq!(expand(".*", "{{{0}}}", &["a"]), ["{a}"]);
+        assert_eq!(expand(".*", "{{n}}", &["a"]), ["{n}"]);
+        assert_eq!(template_error(".*", "a}b"), "A literal '}' must be written as '}}' in the replacement");
+        assert_eq!(template_error(".*", "{0"), "Missing '}' after '{0' in the replacement");
+    }
+
+    #[test]
+    fn only_changed_names_are_planned() {
+        let plan = plan(r"\.jpeg$", ".jpg", &["a.jpeg", "b.jpg", "c.png"]);
+        assert_eq!(plan.renames.len(), 1);
+        assert_eq!(plan.renames[0].from, "a.jpeg");
+        assert_eq!(plan.renames[0].to, "a.jpg");
+        assert_eq!(plan.problem_count(), 0);
+    }
+
+    // (from, to, in_cycle) for each rename
+    fn cycles(plan: &RenamePlan) -> Vec<(&str, &str, bool)> {
+        plan.renames.iter().map(|r| (r.from.to_str().unwrap(), r.to.to_str().unwrap(), r.in_cycle)).collect()
+    }
+
+    fn problems(plan: &RenamePlan) -> Vec<Option<&str>> {
+        plan.renames.iter().map(|r| r.problem.as_deref()).collect()
+    }
+
+    #[test]
+    fn swaps_and_cycles() {
+        let swap = plan("^[0-9]$", "{n}", &["2", "1"]);
+        assert_eq!(cycles(&swap), [("2", "1", true), ("1", "2", true)]);
+        assert_eq!(swap.problem_count(), 0);
+
+        let cycle = plan("^[0-9]$", "{n}", &["3", "1", "2"]);
+        assert_eq!(cycles(&cycle), [("3", "1", true), ("1", "2", true), ("2", "3", true)]);
+        assert_eq!(cycle.problem_count(), 0);
+
+        // a chain ending in a free name is not a cycle, and its names can be taken in turn
+        let chain = plan("^[0-9]$", "{n}", &["2", "3"]);
+        assert_eq!(cycles(&chain), [("2", "1", false), ("3", "2", false)]);
+        assert_eq!(chain.problem_count(), 0);
+
+        // only part of the renames can be in a cycle
+        let mixed = plan("^[0-9]$", "{n}", &["2", "1", "5"]);
+        assert_eq!(cycles(&mixed), [("2", "1", true), ("1", "2", true), ("5", "3", false)]);
+    }
+
+    #[test]
+    fn collisions() {
+        let same = plan(r"\d+", "", &["a1", "a2", "b"]);
+        assert_eq!(problems(&same), [Some("another entry would get the same name"); 2]);
+        assert_eq!(same.problem_count(), 2);
+
+        let taken = plan(r"\.jpeg$", ".jpg", &["a.jpeg", "a.jpg", "b.jpeg"]);
+        assert_eq!(problems(&taken), [Some("an entry with this name already exists"), None]);
+
+        // a name which is free once its entry is renamed is not taken
+        let freed = plan("^a", "ab", &["a", "ab"]);
+        assert_eq!(cycles(&freed), [("a", "ab", false), ("ab", "abb", false)]);
+        assert_eq!(freed.problem_count(), 0);
+        // unless it is the name of an entry which stays as it is
+        let kept = plan("^a$", "{0:upper}", &["a", "A"]);
+        assert_eq!(problems(&kept), [Some("an entry with this name already exists")]);
+
+        let invalid = plan("^[ab]$", "{0}/{0}", &["a", "b"]);
+        assert_eq!(problems(&invalid), [Some("the new name must not be empty or contain path separators"); 2]);
+        let empty = plan(".*", "", &["a"]);
+        assert_eq!(empty.problem_count(), 1);
+    }
+}
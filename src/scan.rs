@@ -0,0 +1,117 @@
+// reading of directory contents, either directly or on a background thread so that the
+// interface does not have to wait for large trees to be read
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use crate::platform;
+
+// a single entry read from a directory
+pub struct ScannedEntry {
+    pub path: PathBuf,
+    pub meta: Option<Metadata>,
+    pub is_dir: bool,
+    pub is_link: bool,
+    // reason the entry could not be fully read, if any
+    pub error: Option<String>,
+}
+
+// the contents of a directory as read by the background scanner
+pub struct ScanResult {
+    pub path: PathBuf,
+    pub entries: io::Result<Vec<ScannedEntry>>,
+}
+
+// short description of an error to be shown next to an entry
+pub fn describe_error(error: &io::Error) -> String {
+    match error.kind() {
+        io::ErrorKind::PermissionDenied => String::from("permission denied"),
+        io::ErrorKind::NotFound => String::from("not found"),
+        _ => error.to_string(),
+    }
+}
+
+// reads the metadata of a file or directory (following symbolic links); if it cannot be read
+// the reason is returned instead, along with whatever metadata could still be read
+pub fn read_metadata(path: &Path) -> (Option<Metadata>, Option<String>) {
+    match path.metadata() {
+        Ok(meta) => (Some(meta), None),
+        Err(error) => match path.symlink_metadata() {
+            Ok(meta) if meta.file_type().is_symlink() => {
+                let reason = if error.kind() == io::ErrorKind::NotFound {
+                    String::from("broken symbolic link")
+                } else {
+                    format!("symbolic link target: {}", describe_error(&error))
+                };
+                (Some(meta), Some(reason))
+            },
+            _ => (None, Some(describe_error(&error))),
+        },
+    }
+}
+
+// reads all entries of a directory (without descending into subdirectories); entries which
+// cannot be read are still returned with the reason
+pub fn read_entries(path: &Path, show_hidden: bool) -> io::Result<Vec<ScannedEntry>> {
+    let mut entries = vec![];
+    for entry in fs::read_dir(path)? {
+        // nothing is known about an entry that failed here, not even its name
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        let path = entry.path();
+        // symbolic links are not followed into when deciding whether this is a directory, but
+        // the metadata shown is that of the link's target
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+        let is_link = entry.file_type().is_ok_and(|t| t.is_symlink());
+        let (meta, error) = read_metadata(&path);
+
+        if !show_hidden && platform::is_hidden(&entry.file_name(), meta.as_ref()) {
+            continue;
+        }
+
+        entries.push(ScannedEntry { path, meta, is_dir, is_link, error });
+    }
+    Ok(entries)
+}
+
+// handle to a worker thread which reads directories in the order they are requested
+pub struct Scanner {
+    requests: Sender<PathBuf>,
+    results: Receiver<ScanResult>,
+}
+
+impl Scanner {
+    pub fn start(show_hidden: bool) -> Self {
+        let (request_tx, request_rx) = mpsc::channel::<PathBuf>();
+        let (result_tx, result_rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            // the thread finishes once the Scanner (and with it the request sender) is dropped
+            for path in request_rx {
+                let entries = read_entries(&path, show_hidden);
+                if result_tx.send(ScanResult { path, entries }).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            requests: request_tx,
+            results: result_rx,
+        }
+    }
+
+    // queues a directory to be read in the background
+    pub fn request(&self, path: PathBuf) {
+        // the worker only stops if the program is exiting, so a failed send can be ignored
+        let _ = self.requests.send(path);
+    }
+
+    // returns a finished scan if one is available, without waiting
+    pub fn try_result(&self) -> Option<ScanResult> {
+        self.results.try_recv().ok()
+    }
+}
@@ -0,0 +1,47 @@
+// shell functions which start DirMan and then change the shell's working directory to the
+// directory DirMan was in when it quit; printed with 'dirman --shell-init <shell>' and meant
+// to be evaluated in the shell's startup file, e.g. 'eval "$(dirman --shell-init bash)"'
+//
+// a program cannot change the working directory of the shell which started it, so DirMan
+// writes the directory to the file given with --cwd-file and the function changes to it
+
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+// bash and zsh understand the same function ('status' is avoided as it is read-only in zsh)
+const POSIX_FUNCTION: &str = r#"dm() {
+    local cwd_file dir ret
+    cwd_file="$(mktemp)" || return
+    command dirman --cwd-file "$cwd_file" "$@"
+    ret=$?
+    if [ -s "$cwd_file" ]; then
+        dir="$(cat -- "$cwd_file")"
+        if [ -d "$dir" ] && [ "$dir" != "$PWD" ]; then
+            cd -- "$dir" || ret=$?
+        fi
+    fi
+    rm -f -- "$cwd_file"
+    return $ret
+}"#;
+
+const FISH_FUNCTION: &str = r#"function dm
+    set -l cwd_file (mktemp); or return
+    command dirman --cwd-file $cwd_file $argv
+    set -l ret $status
+    if test -s $cwd_file
+        set -l dir (cat $cwd_file)
+        if test -d "$dir"; and test "$dir" != "$PWD"
+            cd $dir
+        end
+    end
+    rm -f $cwd_file
+    return $ret
+end"#;
+
+// the function for one of SHELLS
+pub fn function(shell: &str) -> Option<&'static str> {
+    match shell {
+        "bash" | "zsh" => Some(POSIX_FUNCTION),
+        "fish" => Some(FISH_FUNCTION),
+        _ => None,
+    }
+}
@@ -0,0 +1,418 @@
+// colors of the interface: text is given a role (e.g. the current directory, a header) and the
+// theme decides how each role looks when it is drawn, within the colors the terminal supports
+//
+// a style is written as words separated by spaces: a color for the text, 'on' and a color for
+// the background, and any of 'bold', 'underline' and 'reverse' (or 'none' for plain text); a
+// color is one of COLOR_NAMES, a number from 0 to 255, or '#rrggbb', e.g. 'bold #268bd2'
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::io::IsTerminal;
+
+// a color given to text: a role of the theme, or a style of its own (e.g. from LS_COLORS)
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Color {
+    CurrentDir,
+    // a directory about to be removed
+    Remove,
+    // directories to choose from
+    Ambiguous,
+    Closed,
+    Error,
+    Header,
+    Mark,
+    // the border of the selected panel
+    Border,
+    // directories in lists other than the contents panel
+    Directory,
+    // secondary text, e.g. line numbers and notes
+    Muted,
+    // text matched by a search
+    Match,
+    // escape sequences in names
+    Escape,
+    // parts of highlighted source files
+    Keyword,
+    StringLiteral,
+    Number,
+    Comment,
+    Type,
+    Name,
+    Custom(Style),
+}
+
+// names of the roles in the configuration file
+pub const ROLES: &[(&str, Color)] = &[
+    ("current-dir", Color::CurrentDir),
+    ("remove", Color::Remove),
+    ("ambiguous", Color::Ambiguous),
+    ("closed", Color::Closed),
+    ("error", Color::Error),
+    ("header", Color::Header),
+    ("mark", Color::Mark),
+    ("border", Color::Border),
+    ("directory", Color::Directory),
+    ("muted", Color::Muted),
+    ("match", Color::Match),
+    ("escape", Color::Escape),
+    ("keyword", Color::Keyword),
+    ("string", Color::StringLiteral),
+    ("number", Color::Number),
+    ("comment", Color::Comment),
+    ("type", Color::Type),
+    ("name", Color::Name),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Style {
+    pub fg: Option<TermColor>,
+    pub bg: Option<TermColor>,
+    pub bold: bool,
+    pub underline: bool,
+    pub reverse: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum TermColor {
+    // one of the 256 colors of the terminal's palette; the first 16 are the basic colors, which
+    // the terminal may show however it is set up to
+    Ansi(u8),
+    Rgb(u8, u8, u8),
+}
+
+// the basic colors, by their number in the palette
+pub const COLOR_NAMES: &[&str] = &[
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "light-gray",
+    "dark-gray", "light-red", "light-green", "light-yellow", "light-blue", "light-magenta", "light-cyan", "white",
+];
+
+// how many colors the terminal can show
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Depth {
+    // no colors, only bold, underline and reverse
+    None,
+    Basic,
+    Ansi256,
+    TrueColor,
+}
+
+impl Depth {
+    pub const NAMES: &'static [&'static str] = &["auto", "none", "16", "256", "truecolor"];
+
+    // a depth as set in the configuration; None for 'auto'
+    pub fn from_name(name: &str) -> Option<Option<Self>> {
+        match name {
+            "auto" => Some(None),
+            "none" => Some(Some(Depth::None)),
+            "16" => Some(Some(Depth::Basic)),
+            "256" => Some(Some(Depth::Ansi256)),
+            "truecolor" => Some(Some(Depth::TrueColor)),
+            _ => None,
+        }
+    }
+
+    // works out what the terminal supports: nothing if NO_COLOR is set (see no-color.org) or
+    // the output is not a terminal, otherwise from COLORTERM and TERM
+    pub fn detect() -> Self {
+        if env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) || !std::io::stdout().is_terminal() {
+            return Depth::None;
+        }
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            Depth::TrueColor
+        } else if term == "dumb" {
+            Depth::None
+        } else if term.contains("256color") {
+            Depth::Ansi256
+        } else {
+            Depth::Basic
+        }
+    }
+}
+
+pub const THEMES: &[&str] = &["default", "high-contrast", "monochrome", "solarized"];
+
+const DEFAULT_THEME: &[(Color, &str)] = &[
+    (Color::CurrentDir, "blue"),
+    (Color::Remove, "red"),
+    (Color::Ambiguous, "green"),
+    (Color::Closed, "dark-gray"),
+    (Color::Error, "red"),
+    (Color::Header, "cyan"),
+    (Color::Mark, "yellow"),
+    (Color::Border, "red"),
+    (Color::Directory, "blue"),
+    (Color::Muted, "dark-gray"),
+    (Color::Match, "yellow"),
+    (Color::Escape, "magenta"),
+    (Color::Keyword, "magenta"),
+    (Color::StringLiteral, "green"),
+    (Color::Number, "yellow"),
+    (Color::Comment, "dark-gray"),
+    (Color::Type, "cyan"),
+    (Color::Name, "blue"),
+];
+
+// bright colors on the terminal's background, with nothing shown in dark gray
+const HIGH_CONTRAST_THEME: &[(Color, &str)] = &[
+    (Color::CurrentDir, "bold light-cyan"),
+    (Color::Remove, "bold light-red"),
+    (Color::Ambiguous, "bold light-green"),
+    (Color::Closed, "light-gray"),
+    (Color::Error, "bold light-red"),
+    (Color::Header, "bold white"),
+    (Color::Mark, "bold black on light-yellow"),
+    (Color::Border, "bold light-yellow"),
+    (Color::Directory, "bold light-blue"),
+    (Color::Muted, "light-gray"),
+    (Color::Match, "black on light-yellow"),
+    (Color::Escape, "bold light-magenta"),
+    (Color::Keyword, "bold light-magenta"),
+    (Color::StringLiteral, "light-green"),
+    (Color::Number, "light-yellow"),
+    (Color::Comment, "light-gray"),
+    (Color::Type, "light-cyan"),
+    (Color::Name, "light-blue"),
+];
+
+// no colors at all, for terminals without them (or people who do not want them)
+const MONOCHROME_THEME: &[(Color, &str)] = &[
+    (Color::CurrentDir, "bold underline"),
+    (Color::Remove, "reverse"),
+    (Color::Ambiguous, "underline"),
+    (Color::Closed, "none"),
+    (Color::Error, "bold"),
+    (Color::Header, "bold"),
+    (Color::Mark, "reverse"),
+    (Color::Border, "reverse"),
+    (Color::Directory, "bold"),
+    (Color::Muted, "none"),
+    (Color::Match, "reverse"),
+    (Color::Escape, "underline"),
+    (Color::Keyword, "bold"),
+    (Color::StringLiteral, "none"),
+    (Color::Number, "none"),
+    (Color::Comment, "none"),
+    (Color::Type, "none"),
+    (Color::Name, "none"),
+];
+
+// the solarized palette, which needs a terminal with 256 colors or more to look right
+const SOLARIZED_THEME: &[(Color, &str)] = &[
+    (Color::CurrentDir, "bold #268bd2"),
+    (Color::Remove, "#dc322f"),
+    (Color::Ambiguous, "#859900"),
+    (Color::Closed, "#586e75"),
+    (Color::Error, "#dc322f"),
+    (Color::Header, "#2aa198"),
+    (Color::Mark, "#b58900"),
+    (Color::Border, "#cb4b16"),
+    (Color::Directory, "#268bd2"),
+    (Color::Muted, "#586e75"),
+    (Color::Match, "#b58900"),
+    (Color::Escape, "#d33682"),
+    (Color::Keyword, "#859900"),
+    (Color::StringLiteral, "#2aa198"),
+    (Color::Number, "#d33682"),
+    (Color::Comment, "#586e75"),
+    (Color::Type, "#b58900"),
+    (Color::Name, "#268bd2"),
+];
+
+#[derive(Clone)]
+pub struct Theme {
+    styles: HashMap<Color, Style>,
+}
+
+impl Theme {
+    pub fn builtin(name: &str) -> Option<Self> {
+        let theme = match name {
+            "default" => DEFAULT_THEME,
+            "high-contrast" => HIGH_CONTRAST_THEME,
+            "monochrome" => MONOCHROME_THEME,
+            "solarized" => SOLARIZED_THEME,
+            _ => return None,
+        };
+        let styles = theme.iter().map(|(role, style)| (*role, parse_style(style).unwrap())).collect();
+        Some(Self { styles })
+    }
+
+    // changes how a role looks
+    pub fn set(&mut self, role: Color, style: Style) {
+        self.styles.insert(role, style);
+    }
+
+    fn style(&self, color: Color) -> Style {
+        match color {
+            Color::Custom(style) => style,
+            role => self.styles.get(&role).copied().unwrap_or_default(),
+        }
+    }
+}
+
+thread_local! {
+    // the theme and color depth which text is drawn with (only the main thread draws)
+    static ACTIVE: RefCell<(Theme, Depth)> = RefCell::new((Theme::builtin("default").unwrap(), Depth::Basic));
+}
+
+pub fn set_active(theme: Theme, depth: Depth) {
+    ACTIVE.with(|active| *active.borrow_mut() = (theme, depth));
+}
+
+// applies a color to text to be written to the terminal
+pub fn paint(text: &str, color: Color) -> String {
+    ACTIVE.with(|active| {
+        let (theme, depth) = &*active.borrow();
+        let style = theme.style(color);
+
+        let mut codes = vec![];
+        if style.bold {
+            codes.push(String::from("1"));
+        }
+        if style.underline {
+            codes.push(String::from("4"));
+        }
+        if style.reverse {
+            codes.push(String::from("7"));
+        }
+        if let Some(fg) = style.fg.and_then(|fg| color_code(fg, *depth, false)) {
+            codes.push(fg);
+        }
+        if let Some(bg) = style.bg.and_then(|bg| color_code(bg, *depth, true)) {
+            codes.push(bg);
+        }
+
+        if codes.is_empty() {
+            text.to_string()
+        } else {
+            format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text)
+        }
+    })
+}
+
+// the SGR parameters which select a color, as close as the depth allows
+fn color_code(color: TermColor, depth: Depth, background: bool) -> Option<String> {
+    let base = if background { 40 } else { 30 };
+    let extended = if background { 48 } else { 38 };
+    let basic = |n: u8| if n < 8 { base + n as u32 } else { base + 60 + (n - 8) as u32 };
+    match (depth, color) {
+        (Depth::None, _) => None,
+        (_, TermColor::Ansi(n)) if n < 16 => Some(basic(n).to_string()),
+        (Depth::Basic, color) => Some(basic(nearest_basic(to_rgb(color))).to_string()),
+        (Depth::Ansi256, TermColor::Ansi(n)) | (Depth::TrueColor, TermColor::Ansi(n)) => Some(format!("{};5;{}", extended, n)),
+        (Depth::Ansi256, TermColor::Rgb(r, g, b)) => Some(format!("{};5;{}", extended, nearest_256(r, g, b))),
+        (Depth::TrueColor, TermColor::Rgb(r, g, b)) => Some(format!("{};2;{};{};{}", extended, r, g, b)),
+    }
+}
+
+// the usual (xterm) values of the basic colors
+const BASIC_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0), (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+    (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0), (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255),
+];
+
+// levels of the 6x6x6 color cube in the 256 color palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn to_rgb(color: TermColor) -> (u8, u8, u8) {
+    match color {
+        TermColor::Rgb(r, g, b) => (r, g, b),
+        TermColor::Ansi(n) if n < 16 => BASIC_RGB[n as usize],
+        TermColor::Ansi(n) if n < 232 => {
+            let n = n - 16;
+            (CUBE_LEVELS[(n / 36) as usize], CUBE_LEVELS[(n / 6 % 6) as usize], CUBE_LEVELS[(n % 6) as usize])
+        },
+        TermColor::Ansi(n) => {
+            let level = 8 + (n - 232) * 10;
+            (level, level, level)
+        },
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+fn nearest_basic(rgb: (u8, u8, u8)) -> u8 {
+    (0..16).min_by_key(|&n| distance(rgb, BASIC_RGB[n as usize])).unwrap()
+}
+
+// the nearest color of the cube or of the gray ramp of the 256 color palette
+fn nearest_256(r: u8, g: u8, b: u8) -> u8 {
+    let level = |x: u8| (0..6).min_by_key(|&i| (x as i32 - CUBE_LEVELS[i] as i32).abs()).unwrap() as u8;
+    let cube = 16 + 36 * level(r) + 6 * level(g) + level(b);
+    let average = ((r as u32 + g as u32 + b as u32) / 3) as u8;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23);
+    [cube, gray].iter().copied()
+        .min_by_key(|&n| distance((r, g, b), to_rgb(TermColor::Ansi(n))))
+        .unwrap()
+}
+
+// parses a style such as 'bold light-blue' or '#ffffff on #005f87'
+pub fn parse_style(spec: &str) -> Result<Style, String> {
+    let mut style = Style::default();
+    let mut words = spec.split_whitespace();
+    while let Some(word) = words.next() {
+        match word {
+            "none" => {},
+            "bold" => style.bold = true,
+            "underline" => style.underline = true,
+            "reverse" => style.reverse = true,
+            "on" => {
+                let color = words.next().ok_or_else(|| format!("a color must follow 'on' in '{}'", spec))?;
+                style.bg = Some(parse_color(color)?);
+            },
+            _ => style.fg = Some(parse_color(word)?),
+        }
+    }
+    Ok(style)
+}
+
+fn parse_color(word: &str) -> Result<TermColor, String> {
+    if let Some(n) = COLOR_NAMES.iter().position(|name| *name == word) {
+        return Ok(TermColor::Ansi(n as u8));
+    }
+    if let Ok(n) = word.parse::<u8>() {
+        return Ok(TermColor::Ansi(n));
+    }
+    if let Some(hex) = word.strip_prefix('#').filter(|hex| hex.len() == 6 && hex.is_ascii()) {
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+        if let (Ok(r), Ok(g), Ok(b)) = (channel(0), channel(2), channel(4)) {
+            return Ok(TermColor::Rgb(r, g, b));
+        }
+    }
+    Err(format!("unknown color '{}'; expected a color name, a number from 0 to 255 or #rrggbb", word))
+}
+
+// parses the parameters of an SGR escape sequence, as used in LS_COLORS (e.g. '01;38;5;208');
+// parameters other than colors, bold, underline and reverse are left out
+pub fn parse_sgr(codes: &str) -> Option<Style> {
+    let mut style = Style::default();
+    let mut codes = codes.split(';').map(|code| if code.is_empty() { Some(0) } else { code.parse::<u8>().ok() });
+    while let Some(code) = codes.next() {
+        match code? {
+            0 => style = Style::default(),
+            1 => style.bold = true,
+            4 => style.underline = true,
+            7 => style.reverse = true,
+            n @ 30..=37 => style.fg = Some(TermColor::Ansi(n - 30)),
+            n @ 40..=47 => style.bg = Some(TermColor::Ansi(n - 40)),
+            n @ 90..=97 => style.fg = Some(TermColor::Ansi(n - 90 + 8)),
+            n @ 100..=107 => style.bg = Some(TermColor::Ansi(n - 100 + 8)),
+            39 => style.fg = None,
+            49 => style.bg = None,
+            n @ (38 | 48) => {
+                let color = match codes.next()?? {
+                    5 => TermColor::Ansi(codes.next()??),
+                    2 => TermColor::Rgb(codes.next()??, codes.next()??, codes.next()??),
+                    _ => return None,
+                };
+                if n == 38 { style.fg = Some(color) } else { style.bg = Some(color) }
+            },
+            _ => {},
+        }
+    }
+    Some(style)
+}
@@ -0,0 +1,213 @@
+// moving files and directories to the trash instead of deleting them
+//
+// the trash follows the FreeDesktop trash specification: trashed entries are kept in its 'files'
+// directory, and for every entry an '<name>.trashinfo' file in its 'info' directory records
+// where the entry came from and when it was trashed
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use chrono::Local;
+use crate::fsops;
+use crate::journal::Change;
+use crate::names;
+use crate::platform;
+
+// an entry in the trash
+pub struct TrashEntry {
+    // name of the entry in the trash (which is not always the original name)
+    pub name: OsString,
+    pub original_path: PathBuf,
+    // as recorded in the information file, e.g. '2020-06-01T14:30:00'
+    pub deletion_date: String,
+}
+
+pub struct Trash {
+    dir: PathBuf,
+}
+
+impl Trash {
+    // returns the user's trash; None if its location cannot be determined
+    pub fn open() -> Option<Self> {
+        platform::trash_dir().map(|dir| Self { dir })
+    }
+
+    fn files_dir(&self) -> PathBuf {
+        self.dir.join("files")
+    }
+
+    fn info_dir(&self) -> PathBuf {
+        self.dir.join("info")
+    }
+
+    fn info_path(&self, name: &OsStr) -> PathBuf {
+        let mut file_name = name.to_os_string();
+        file_name.push(".trashinfo");
+        self.info_dir().join(file_name)
+    }
+
+    // moves a file or directory into the trash
+    pub fn put(&self, path: PathBuf) -> io::Result<Change> {
+        fs::create_dir_all(self.files_dir())?;
+        fs::create_dir_all(self.info_dir())?;
+
+        let name = path.file_name().unwrap().to_os_string();
+        let mut number = 1;
+        loop {
+            // an entry with the same name may already be in the trash, so number the new one
+            let trash_name = if number == 1 { name.clone() } else { numbered_name(&name, number) };
+            number += 1;
+
+            let trashed = self.files_dir().join(&trash_name);
+            if trashed.symlink_metadata().is_ok() {
+                continue;
+            }
+            // the information file is created first so that another program trashing an
+            // entry with the same name at the same time cannot take the name
+            let info = self.info_path(&trash_name);
+            match write_info(&info, &path) {
+                Ok(()) => {},
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(error) => return Err(error),
+            }
+
+            // entries on other filesystems are copied into the home trash rather than using
+            // the specification's per-filesystem trash directories
+            if let Err(error) = fsops::move_path(&path, &trashed) {
+                let _ = fs::remove_file(&info);
+                return Err(error);
+            }
+            return Ok(Change::Trashed { path, trashed, info });
+        }
+    }
+
+    // lists the entries in the trash, sorted by name
+    pub fn list(&self) -> io::Result<Vec<TrashEntry>> {
+        let info_files = match fs::read_dir(self.info_dir()) {
+            Ok(info_files) => info_files,
+            // nothing has been trashed yet
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(error) => return Err(error),
+        };
+
+        let mut entries = vec![];
+        for info_file in info_files {
+            let info_file = info_file?.path();
+            if info_file.extension() != Some(OsStr::new("trashinfo")) {
+                continue;
+            }
+            let name = info_file.file_stem().unwrap().to_os_string();
+            // information files without an entry are left over from interrupted operations
+            if self.files_dir().join(&name).symlink_metadata().is_err() {
+                continue;
+            }
+            if let Some((original_path, deletion_date)) = read_info(&info_file) {
+                entries.push(TrashEntry { name, original_path, deletion_date });
+            }
+        }
+        entries.sort_by(|x, y| x.name.cmp(&y.name));
+        Ok(entries)
+    }
+
+    // moves an entry out of the trash back to where it came from; returns the entry's path in
+    // the trash and its restored path
+    pub fn restore(&self, name: &OsStr) -> io::Result<(PathBuf, PathBuf)> {
+        let entry = self.list()?.into_iter().find(|entry| entry.name == name)
+            .ok_or_else(|| io::Error::other(format!("'{}' is not in the trash", names::display_name(name))))?;
+
+        let trashed = self.files_dir().join(name);
+        let original = entry.original_path;
+        if original.symlink_metadata().is_ok() {
+            return Err(io::Error::other(format!("'{}' already exists", names::display_name(original.as_os_str()))));
+        }
+        if !original.parent().is_some_and(Path::is_dir) {
+            return Err(io::Error::other(format!("the directory of '{}' no longer exists", names::display_name(original.as_os_str()))));
+        }
+
+        fsops::move_path(&trashed, &original)?;
+        fs::remove_file(self.info_path(name))?;
+        Ok((trashed, original))
+    }
+
+    // permanently removes everything in the trash; returns how many entries were removed
+    pub fn empty(&self) -> io::Result<usize> {
+        let entries = self.list()?;
+        for entry in &entries {
+            let trashed = self.files_dir().join(&entry.name);
+            if trashed.symlink_metadata()?.is_dir() {
+                fs::remove_dir_all(&trashed)?;
+            } else {
+                fs::remove_file(&trashed)?;
+            }
+            fs::remove_file(self.info_path(&entry.name))?;
+        }
+        // cache of the sizes of trashed directories (see the specification)
+        let _ = fs::remove_file(self.dir.join("directorysizes"));
+        Ok(entries.len())
+    }
+}
+
+// writes the information file for an entry being trashed from the given path; fails if the
+// information file already exists
+pub fn write_info(info: &Path, original: &Path) -> io::Result<()> {
+    use std::io::Write;
+
+    let mut file = fs::OpenOptions::new().write(true).create_new(true).open(info)?;
+    write!(file, "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        encode_path(original), Local::now().format("%Y-%m-%dT%H:%M:%S"))
+}
+
+// reads the original path and deletion date from an information file
+fn read_info(info: &Path) -> Option<(PathBuf, String)> {
+    let text = fs::read_to_string(info).ok()?;
+    let mut path = None;
+    let mut deletion_date = String::new();
+    for line in text.lines() {
+        if let Some(value) = line.strip_prefix("Path=") {
+            path = decode_path(value);
+        } else if let Some(value) = line.strip_prefix("DeletionDate=") {
+            deletion_date = value.to_string();
+        }
+    }
+    Some((path?, deletion_date))
+}
+
+// 'Path' values are escaped like the path of a URL
+fn encode_path(path: &Path) -> String {
+    let mut encoded = String::new();
+    for byte in platform::path_bytes(path) {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn decode_path(text: &str) -> Option<PathBuf> {
+    let mut bytes = vec![];
+    let mut rest = text.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(after.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &after[2..];
+        } else {
+            bytes.push(byte);
+            rest = after;
+        }
+    }
+    Some(platform::path_from_bytes(bytes))
+}
+
+// numbers a name while keeping its extension, e.g. 'notes.2.txt'
+fn numbered_name(name: &OsStr, number: usize) -> OsString {
+    let path = Path::new(name);
+    let mut numbered = path.file_stem().unwrap_or(name).to_os_string();
+    numbered.push(format!(".{}", number));
+    if let Some(ext) = path.extension() {
+        numbered.push(".");
+        numbered.push(ext);
+    }
+    numbered
+}